@@ -0,0 +1,308 @@
+use std::io::{
+    Result,
+    Read,
+    Seek,
+    SeekFrom,
+};
+
+use byteorder::{
+    ReadBytesExt,
+    LittleEndian,
+};
+
+use bitmap;
+use bitmap::new_data_error;
+use icon;
+use riff::{
+    ChunkHeader,
+    read_id,
+};
+
+const FLAG_ICON: u32 = 0x01; // Frames are icons or cursors instead of raw bitmaps
+
+/// A single step of an animated cursor, showing the given frame for `rate` jiffies (1/60 s).
+#[derive( Debug, PartialEq, Eq, Clone, Copy )]
+pub struct AnimationStep {
+    pub frame: usize,
+    pub rate: u32,
+}
+
+/// The contents of a Windows animated cursor file.
+///
+/// Frames are decoded by index, the steps describe in which order and for how long they
+/// are displayed. Size and bits per pixel are zero unless the file states them explicitly.
+#[derive( Debug, PartialEq, Eq, Clone )]
+pub struct Animation {
+    pub width: u32,
+    pub height: u32,
+    pub bpp: u32,
+    pub steps: Vec<AnimationStep>,
+    frames: Vec<( u64, u32 )>, // Offset and size of each frame
+    icon: bool,
+}
+
+impl Animation {
+    /// Returns the number of frames, which may differ from the number of steps.
+    pub fn frames( &self ) -> usize {
+        self.frames.len()
+    }
+}
+
+struct AnimationHeader {
+    frames: u32,
+    steps: u32,
+    width: u32,
+    height: u32,
+    bpp: u32,
+    planes: u32,
+    rate: u32,
+    flags: u32,
+}
+
+impl AnimationHeader {
+    fn from_reader( input: &mut Read ) -> Result<AnimationHeader> {
+        let size = input.read_u32::<LittleEndian>()?;
+        if size != 36 {
+            return Err( new_data_error( format!( "Invalid animation header size {}", size ) ) );
+        }
+
+        let frames = input.read_u32::<LittleEndian>()?;
+        let steps = input.read_u32::<LittleEndian>()?;
+        let width = input.read_u32::<LittleEndian>()?;
+        let height = input.read_u32::<LittleEndian>()?;
+        let bpp = input.read_u32::<LittleEndian>()?;
+        let planes = input.read_u32::<LittleEndian>()?;
+        let rate = input.read_u32::<LittleEndian>()?;
+        let flags = input.read_u32::<LittleEndian>()?;
+
+        Ok( AnimationHeader {
+            frames,
+            steps,
+            width,
+            height,
+            bpp,
+            planes,
+            rate,
+            flags,
+        } )
+    }
+}
+
+fn read_values( input: &mut Read, size: u32 ) -> Result<Vec<u32>> {
+    let mut values = Vec::new();
+    for _ in 0..( size / 4 ) {
+        values.push( input.read_u32::<LittleEndian>()? );
+    }
+
+    Ok( values )
+}
+
+// The sequence and rates hold a value for every step
+fn check_steps( name: &str, values: &Option<Vec<u32>>, steps: u32 ) -> Result<()> {
+    match *values {
+        Some( ref v ) if v.len() != steps as usize => Err( new_data_error( format!(
+            "Animated cursor {} has {} steps instead of {}", name, v.len(), steps ) ) ),
+        _ => Ok( () ),
+    }
+}
+
+pub( crate ) fn read_animation<TInput: Read + Seek>( input: &mut TInput ) -> Result<Animation> {
+    // Read RIFF header
+    let riff = ChunkHeader::from_reader( input )?;
+    if &riff.id != b"RIFF" || &read_id( input )? != b"ACON" {
+        return Err( new_data_error( "Invalid animated cursor header" ) );
+    }
+
+    let mut position = input.stream_position()?;
+    let end = position + ( riff.size as u64 ).saturating_sub( 4 );
+
+    let mut header = None;
+    let mut rates = None;
+    let mut sequence = None;
+    let mut frames = Vec::new();
+
+    // Read chunks
+    while position + 8 <= end {
+        let chunk = ChunkHeader::from_reader( input )?;
+        let data = position + 8;
+
+        match &chunk.id {
+            b"anih" => header = Some( AnimationHeader::from_reader( input )? ),
+            b"rate" => rates = Some( read_values( input, chunk.size )? ),
+            b"seq " => sequence = Some( read_values( input, chunk.size )? ),
+            b"LIST" if &read_id( input )? == b"fram" => {
+                let mut frame = data + 4;
+                while frame + 8 <= data + chunk.size as u64 {
+                    input.seek( SeekFrom::Start( frame ) )?;
+                    let icon = ChunkHeader::from_reader( input )?;
+
+                    if &icon.id == b"icon" {
+                        frames.push( ( frame + 8, icon.size ) );
+                    }
+                    frame += 8 + icon.padded_size();
+                }
+            },
+            _ => {},
+        }
+
+        position = data + chunk.padded_size();
+        input.seek( SeekFrom::Start( position ) )?;
+    }
+
+    let header = header.ok_or( new_data_error( "Animated cursor has no anih header" ) )?;
+    if frames.len() != header.frames as usize {
+        return Err( new_data_error( format!(
+            "Animated cursor has {} frames instead of {}", frames.len(), header.frames ) ) );
+    }
+
+    check_steps( "sequence", &sequence, header.steps )?;
+    check_steps( "rates", &rates, header.steps )?;
+
+    // Without a sequence the frames are shown in order, each at the default rate
+    let mut steps = Vec::new();
+    for step in 0..( header.steps as usize ) {
+        let frame = match sequence {
+            Some( ref s ) => s[ step ] as usize,
+            None => step,
+        };
+
+        let rate = match rates {
+            Some( ref r ) => r[ step ],
+            None => header.rate,
+        };
+
+        if frame >= frames.len() {
+            return Err( new_data_error( format!( "Invalid animated cursor frame {}", frame ) ) );
+        }
+
+        steps.push( AnimationStep {
+            frame,
+            rate,
+        } );
+    }
+
+    Ok( Animation {
+        width: header.width,
+        height: header.height,
+        bpp: header.bpp * header.planes.max( 1 ),
+        steps,
+        frames,
+        icon: header.flags & FLAG_ICON != 0,
+    } )
+}
+
+// Decodes a single frame, which is either an icon or cursor file or a packed bitmap.
+// Icon files holding more than one image are represented by their first image.
+pub( crate ) fn decode_frame<TInput: Read + Seek, TBuilder: super::Builder>(
+    input: &mut TInput, animation: &Animation, frame: usize, builder: TBuilder ) -> Result<TBuilder> {
+
+    let ( offset, _ ) = *animation.frames.get( frame )
+        .ok_or( new_data_error( format!( "Invalid animated cursor frame {}", frame ) ) )?;
+
+    input.seek( SeekFrom::Start( offset ) )?;
+
+    match animation.icon {
+        true => {
+            let entries = icon::read_icon_dir( input )?;
+            let entry = entries.first()
+                .ok_or( new_data_error( format!( "Animated cursor frame {} is empty", frame ) ) )?;
+
+            icon::decode_entry( input, entry, builder )
+        },
+        false => bitmap::decode_dib( input, builder ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use byteorder::{
+        WriteBytesExt,
+        LittleEndian,
+    };
+
+    use canvas::Canvas;
+    use super::{
+        read_animation,
+        decode_frame,
+    };
+
+    fn chunk( id: &[u8], data: &[u8] ) -> Vec<u8> {
+        let mut chunk = id.to_vec();
+        chunk.write_u32::<LittleEndian>( data.len() as u32 ).unwrap();
+        chunk.extend_from_slice( data );
+        if !data.len().is_multiple_of( 2 ) {
+            chunk.push( 0 );
+        }
+
+        chunk
+    }
+
+    fn values( values: &[u32] ) -> Vec<u8> {
+        let mut data = Vec::new();
+        for &value in values {
+            data.write_u32::<LittleEndian>( value ).unwrap();
+        }
+
+        data
+    }
+
+    // An animated cursor with two 1x1 bitmap frames, whose blue values are 0x40 and 0x80
+    fn animation( steps: u32, sequence: Option<&[u32]>, rates: Option<&[u32]> ) -> Vec<u8> {
+        let mut body = b"ACON".to_vec();
+        body.extend( chunk( b"anih", &values( &[ 36, 2, steps, 0, 0, 0, 0, 10, 0 ] ) ) );
+        if let Some( rates ) = rates {
+            body.extend( chunk( b"rate", &values( rates ) ) );
+        }
+        if let Some( sequence ) = sequence {
+            body.extend( chunk( b"seq ", &values( sequence ) ) );
+        }
+
+        let mut list = b"fram".to_vec();
+        for &blue in [ 0x40, 0x80 ].iter() {
+            let mut frame = values( &[ 40, 1, 1 ] );
+            frame.extend_from_slice( &[ 1, 0, 24, 0 ] );
+            frame.resize( 40, 0 );
+            frame.extend_from_slice( &[ blue, 0, 0, 0 ] );
+            list.extend( chunk( b"icon", &frame ) );
+        }
+        body.extend( chunk( b"LIST", &list ) );
+
+        chunk( b"RIFF", &body )
+    }
+
+    #[test]
+    fn reads_steps_and_decodes_frames() {
+        let file = animation( 3, Some( &[ 1, 0, 1 ] ), Some( &[ 5, 6, 7 ] ) );
+        let mut input = Cursor::new( &file[ .. ] );
+        let animation = read_animation( &mut input ).unwrap();
+
+        assert_eq!( animation.frames(), 2 );
+        let steps: Vec<_> = animation.steps.iter().map( |s| ( s.frame, s.rate ) ).collect();
+        assert_eq!( steps, [ ( 1, 5 ), ( 0, 6 ), ( 1, 7 ) ] );
+
+        let canvas = decode_frame( &mut input, &animation, 1, Canvas::new() ).unwrap();
+        assert_eq!( canvas.pixel( 0, 0 ), ( 0, 0, 0x80, 255 ) );
+    }
+
+    #[test]
+    fn default_sequence_and_rate() {
+        let file = animation( 2, None, None );
+        let animation = read_animation( &mut Cursor::new( &file[ .. ] ) ).unwrap();
+
+        let steps: Vec<_> = animation.steps.iter().map( |s| ( s.frame, s.rate ) ).collect();
+        assert_eq!( steps, [ ( 0, 10 ), ( 1, 10 ) ] );
+    }
+
+    #[test]
+    fn step_count_mismatch_is_an_error() {
+        for file in [
+            animation( 0xFFFFFFFF, None, None ),
+            animation( 0xFFFFFFFF, Some( &[ 0, 1 ] ), None ),
+            animation( 2, Some( &[ 0, 1 ] ), Some( &[ 5 ] ) ),
+        ].iter() {
+            assert!( read_animation( &mut Cursor::new( &file[ .. ] ) ).is_err() );
+        }
+    }
+}
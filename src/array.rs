@@ -0,0 +1,116 @@
+use std::io::{
+    Result,
+    Read,
+    Seek,
+    SeekFrom,
+};
+
+use byteorder::{
+    ReadBytesExt,
+    LittleEndian,
+};
+
+use bitmap;
+use bitmap::{
+    FileType,
+    new_data_error,
+};
+use pointer;
+
+/// A single image within an OS/2 bitmap array.
+///
+/// The display size is the resolution of the display the image is intended for
+/// and is zero for device independent images.
+#[derive( Debug, PartialEq, Eq, Clone, Copy )]
+pub struct ArrayEntry {
+    pub display_width: u16,
+    pub display_height: u16,
+    pub width: u32,
+    pub height: u32,
+    pub bpp: u32,
+    base: u64, // Position of the bitmap array within the input
+    offset: u64, // Position of the entry file header within the input
+}
+
+pub( crate ) fn read_array<TInput: Read + Seek>( input: &mut TInput ) -> Result<Vec<ArrayEntry>> {
+    let base = input.stream_position()?;
+    let mut offset = base;
+    let mut entries = Vec::new();
+
+    loop {
+        input.seek( SeekFrom::Start( offset ) )?;
+
+        // Read array header
+        if FileType::from_reader( input )? != FileType::BitmapArray {
+            return Err( new_data_error(
+                format!( "Invalid bitmap array header at 0x{:X}", offset ) ) );
+        }
+
+        input.read_u32::<LittleEndian>()?; // Size of the array header
+        let next = input.read_u32::<LittleEndian>()?;
+        let display_width = input.read_u16::<LittleEndian>()?;
+        let display_height = input.read_u16::<LittleEndian>()?;
+
+        // Read entry headers
+        let position = input.stream_position()?;
+        let file = bitmap::read_file_header( input )?;
+        let ( width, height, bpp ) = match file.file_type {
+            FileType::DeviceIndependentBitmap => {
+                let header = bitmap::read_header( input )?;
+                ( header.core.width, header.core.height, header.core.bpp )
+            },
+            FileType::BitmapArray => return Err( new_data_error(
+                "Nested bitmap arrays are not supported" ) ),
+            _ => pointer::read_header( input, file )?.dimensions(),
+        };
+
+        entries.push( ArrayEntry {
+            display_width,
+            display_height,
+            width,
+            height,
+            bpp,
+            base,
+            offset: position,
+        } );
+
+        if next == 0 {
+            break;
+        }
+
+        // Entries are linked in ascending order, anything else would loop forever
+        let next = base + next as u64;
+        if next <= offset {
+            return Err( new_data_error(
+                format!( "Invalid next bitmap array offset 0x{:X}", next ) ) );
+        }
+        offset = next;
+    }
+
+    Ok( entries )
+}
+
+pub( crate ) fn decode_entry<TInput: Read + Seek, TBuilder: super::Builder>(
+    input: &mut TInput, entry: &ArrayEntry, mut builder: TBuilder ) -> Result<TBuilder> {
+
+    input.seek( SeekFrom::Start( entry.offset ) )?;
+    let file = bitmap::read_file_header( input )?;
+
+    match file.file_type {
+        FileType::DeviceIndependentBitmap => {
+            let header = bitmap::read_header( input )?;
+
+            // All offsets within a bitmap array are relative to the array itself
+            let offset = entry.base + file.data_offset as u64;
+            input.seek( SeekFrom::Start( offset ) )?;
+            bitmap::decode_pixels( input, &header, offset, &mut builder )?;
+
+            Ok( builder )
+        },
+        FileType::BitmapArray => Err( new_data_error( "Nested bitmap arrays are not supported" ) ),
+        _ => {
+            let header = pointer::read_header( input, file )?;
+            pointer::decode_pixels( input, entry.base, &header, builder )
+        },
+    }
+}
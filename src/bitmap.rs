@@ -7,100 +7,254 @@ use std::io::{
 };
 
 use byteorder::{
+    ByteOrder,
     ReadBytesExt,
     LittleEndian,
 };
 
-fn new_data_error<S>( message: S ) -> io::Error
+use huffman;
+use riff;
+use embedded::{
+    Embedded,
+    EmbeddedKind,
+};
+
+pub( crate ) const FILE_HEADER_SIZE: u64 = 14;
+
+pub( crate ) fn new_data_error<S>( message: S ) -> io::Error
     where S: Into<String> {
 
     io::Error::new( io::ErrorKind::InvalidData, message.into() )
 }
 
-#[derive( PartialEq, Eq, Clone, Copy )]
-enum FileType {
-    // DDB, // Denotes a device dependant bitmap file
+// Rejects file types that the given function can't handle, naming the one that does
+pub( crate ) fn new_unsupported_error( function: &str, file_type: FileType ) -> io::Error {
+    let ( kind, alternative ) = match file_type {
+        FileType::DeviceDependentBitmap => ( "device dependent bitmaps", "decode" ),
+        FileType::DeviceIndependentBitmap => ( "device independent bitmaps", "decode" ),
+        FileType::BitmapArray => ( "bitmap arrays", "read_array" ),
+        FileType::ColorIcon | FileType::ColorPointer | FileType::Icon | FileType::Pointer
+            => ( "icons and pointers", "decode_pointer" ),
+        FileType::Riff => ( "RIFF bitmaps", "decode" ),
+    };
+
+    new_data_error( format!( "{} doesn't handle {}, use bmp_rs::{}", function, kind, alternative ) )
+}
+
+/// The type of a bitmap file as given by its signature.
+#[derive( Debug, PartialEq, Eq, Clone, Copy )]
+pub enum FileType {
+    DeviceDependentBitmap, // Denotes a device dependant bitmap file
     DeviceIndependentBitmap, // Denotes a device independent bitmap file
-    // BA, // Denotes a bitmap array
-    // CI, // Denotes a color icon
-    // CP, // Denotes a color pointer
-    // IC, // Denotes a icon
-    // PT, // Denotes a pointer
+    BitmapArray, // Denotes a bitmap array
+    ColorIcon, // Denotes a color icon
+    ColorPointer, // Denotes a color pointer
+    Icon, // Denotes a icon
+    Pointer, // Denotes a pointer
+    Riff, // Denotes a RIFF container around a bitmap
 }
 
-#[derive( PartialEq, Eq, Clone, Copy )]
-enum Version {
+/// The version of a bitmap's info header, which is determined by its size.
+#[derive( Debug, PartialEq, Eq, Clone, Copy )]
+pub enum Version {
     Microsoft2,
     Microsoft3,
+    Adobe2, // Undocumented BITMAPV2INFOHEADER with RGB masks written by Adobe Photoshop
+    Adobe3, // Undocumented BITMAPV3INFOHEADER with RGBA masks written by Adobe Photoshop
     Microsoft4,
     Microsoft5,
+    Os2( u32 ), // IBM OS/2 2.x header of the given size
 }
 
-#[derive( PartialEq, Eq, Clone, Copy )]
-enum Compression {
+/// The compression of a bitmap's pixel data. Uncompressed bitmaps have none.
+#[derive( Debug, PartialEq, Eq, Clone, Copy )]
+pub enum Compression {
     RunLength8,
     RunLength4,
     Bitmask,
+    Huffman1D,
+    RunLength24,
+    AlphaBitmask,
+    Jpeg,
+    Png,
+    Cmyk,
+    CmykRunLength8,
+    CmykRunLength4,
 }
 
-struct CoreHeader {
-    version: Version,
+/// The color space of a bitmap with a Version 4 or 5 header.
+#[derive( Debug, PartialEq, Eq, Clone, Copy )]
+pub enum ColorSpace {
+    CalibratedRgb, // Given by the endpoints and gamma values of the header
+    Srgb,
+    Windows, // The default color space of the system
+    Linked, // Given by an ICC profile stored elsewhere
+    Embedded, // Given by an ICC profile stored within the file
+}
+
+/// The rendering intent of a bitmap with a Version 5 header.
+#[derive( Debug, PartialEq, Eq, Clone, Copy )]
+pub enum Intent {
+    Business, // Saturation
+    Graphics, // Relative colorimetric
+    Images, // Perceptual
+    AbsoluteColorimetric,
+}
+
+pub( crate ) struct FileHeader {
+    pub( crate ) file_type: FileType,
+    pub( crate ) file_size: u32,
+    pub( crate ) hotspot_x: i16,
+    pub( crate ) hotspot_y: i16,
+    pub( crate ) data_offset: u32,
+}
+
+struct DeviceHeader {
     width: u32,
     height: u32,
+    byte_width: u32,
+    planes: u32,
     bpp: u32,
-    planes: u16,
-    top_down: bool,
-}
-
-struct InfoHeader {
-    compression: Option<Compression>,
-    image_size: u32,
-    ppm_x: i32,
-    ppm_y: i32,
-    used_colors: u32,
-    important_colors: u32,
-}
-
-struct BitfieldMask {
-    red: u32,
-    green: u32,
-    blue: u32,
-    alpha: u32,
-}
-
-struct ExtraHeader {
-    color_space_type: u32,
-    red_x: i32,
-    red_y: i32,
-    red_z: i32,
-    green_x: i32,
-    green_y: i32,
-    green_z: i32,
-    blue_x: i32,
-    blue_y: i32,
-    blue_z: i32,
-    gamma_red: u32,
-    gamma_green: u32,
-    gamma_blue: u32,
-}
-
-struct ProfileHeader {
-    intent: u32,
-    data: u32,
-    size: u32,
+}
+
+pub( crate ) struct CoreHeader {
+    version: Version,
+    pub( crate ) width: u32,
+    pub( crate ) height: u32,
+    pub( crate ) bpp: u32,
+    pub( crate ) planes: u16,
+    pub( crate ) top_down: bool,
+}
+
+pub( crate ) struct InfoHeader {
+    pub( crate ) compression: Option<Compression>,
+    pub( crate ) image_size: u32,
+    pub( crate ) ppm_x: i32,
+    pub( crate ) ppm_y: i32,
+    pub( crate ) used_colors: u32,
+    pub( crate ) important_colors: u32,
+}
+
+pub( crate ) struct Os2Header {
+    pub( crate ) units: u16,
+    pub( crate ) rendering: u16,
+    pub( crate ) size1: u32,
+    pub( crate ) size2: u32,
+    pub( crate ) identifier: u32,
+}
+
+pub( crate ) struct BitfieldMask {
+    pub( crate ) red: u32,
+    pub( crate ) green: u32,
+    pub( crate ) blue: u32,
+    pub( crate ) alpha: u32,
+}
+
+pub( crate ) struct ExtraHeader {
+    pub( crate ) color_space_type: u32,
+    pub( crate ) red_x: i32,
+    pub( crate ) red_y: i32,
+    pub( crate ) red_z: i32,
+    pub( crate ) green_x: i32,
+    pub( crate ) green_y: i32,
+    pub( crate ) green_z: i32,
+    pub( crate ) blue_x: i32,
+    pub( crate ) blue_y: i32,
+    pub( crate ) blue_z: i32,
+    pub( crate ) gamma_red: u32,
+    pub( crate ) gamma_green: u32,
+    pub( crate ) gamma_blue: u32,
+}
+
+pub( crate ) struct ProfileHeader {
+    pub( crate ) intent: u32,
+    pub( crate ) data: u32,
+    pub( crate ) size: u32,
     reserved: u32,
 }
 
+pub( crate ) struct Header {
+    pub( crate ) version: Version,
+    pub( crate ) core: CoreHeader,
+    pub( crate ) info: Option<InfoHeader>,
+    pub( crate ) bitmask: BitfieldMask,
+    pub( crate ) os2: Option<Os2Header>,
+    pub( crate ) extra: Option<ExtraHeader>,
+    pub( crate ) profile: Option<ProfileHeader>,
+    pub( crate ) palette: Vec<u8>,
+}
+
+impl Header {
+    // Size of all headers including bitfield masks and palette. Within a packed bitmap
+    // this is the offset of the pixel data.
+    pub( crate ) fn size( &self ) -> u32 {
+        let compression = match self.info {
+            Some( ref i ) => i.compression,
+            None => None,
+        };
+
+        let masks = match self.version {
+            Version::Microsoft3 => match compression {
+                Some( Compression::Bitmask ) => 12,
+                Some( Compression::AlphaBitmask ) => 16,
+                _ => 0,
+            },
+            _ => 0,
+        };
+
+        // The palette holds as many bytes per color as the file does
+        self.version.size() + masks + self.palette.len() as u32
+    }
+
+    // Icons and cursors keep an alpha channel in the otherwise unused byte of 32-bit pixels
+    pub( crate ) fn set_implicit_alpha( &mut self ) {
+        let uncompressed = match self.info {
+            Some( ref i ) => i.compression.is_none(),
+            None => true,
+        };
+
+        if self.core.bpp == 32 && uncompressed {
+            self.bitmask.alpha = 0xFF000000;
+        }
+    }
+}
+
+impl ColorSpace {
+    pub( crate ) fn from_u32( value: u32 ) -> Option<ColorSpace> {
+        match value {
+            0 => Some( ColorSpace::CalibratedRgb ),
+            0x73524742 => Some( ColorSpace::Srgb ), // 'sRGB'
+            0x57696E20 => Some( ColorSpace::Windows ), // 'Win '
+            0x4C494E4B => Some( ColorSpace::Linked ), // 'LINK'
+            0x4D424544 => Some( ColorSpace::Embedded ), // 'MBED'
+            _ => None,
+        }
+    }
+}
+
+impl Intent {
+    pub( crate ) fn from_u32( value: u32 ) -> Option<Intent> {
+        match value {
+            1 => Some( Intent::Business ),
+            2 => Some( Intent::Graphics ),
+            4 => Some( Intent::Images ),
+            8 => Some( Intent::AbsoluteColorimetric ),
+            _ => None,
+        }
+    }
+}
+
 impl FileType {
-    fn from_reader( input: &mut Read ) -> Result<FileType> {
+    pub( crate ) fn from_reader( input: &mut Read ) -> Result<FileType> {
         match input.read_u16::<LittleEndian>()? {
-            // 0 => Ok( FileType::DDB ), // TODO: Enable for MS Version 1 Bitmaps
+            0 => Ok( FileType::DeviceDependentBitmap ),
             0x4D42 => Ok( FileType::DeviceIndependentBitmap ),
-            // 0x4142 => Ok( FileType::BA ),
-            // 0x4943 => Ok( FileType::CI ),
-            // 0x5043 => Ok( FileType::CP ),
-            // 0x4349 => Ok( FileType::IC ),
-            // 0x5450 => Ok( FileType::PT ),
+            0x4142 => Ok( FileType::BitmapArray ),
+            0x4943 => Ok( FileType::ColorIcon ),
+            0x5043 => Ok( FileType::ColorPointer ),
+            0x4349 => Ok( FileType::Icon ),
+            0x5450 => Ok( FileType::Pointer ),
+            0x4952 => Ok( FileType::Riff ),
             x @ _ => Err( new_data_error(
                 format!( "Invalid file type 0x{:X}", x ) ) ),
         }
@@ -112,20 +266,45 @@ impl Version {
         match input.read_u32::<LittleEndian>()? {
             0x0C => Ok( Version::Microsoft2 ),
             0x28 => Ok( Version::Microsoft3 ),
+            0x34 => Ok( Version::Adobe2 ),
+            0x38 => Ok( Version::Adobe3 ),
             0x6C => Ok( Version::Microsoft4 ),
             0x7C => Ok( Version::Microsoft5 ),
+            x @ 0x10 ..= 0x40 if x % 2 == 0 => Ok( Version::Os2( x ) ),
             x @ _ => Err( new_data_error(
                 format!( "Invalid header size 0x{:X}", x ) ) ),
         }
     }
+
+    fn size( &self ) -> u32 {
+        match *self {
+            Version::Microsoft2 => 0x0C,
+            Version::Microsoft3 => 0x28,
+            Version::Adobe2 => 0x34,
+            Version::Adobe3 => 0x38,
+            Version::Microsoft4 => 0x6C,
+            Version::Microsoft5 => 0x7C,
+            Version::Os2( size ) => size,
+        }
+    }
 }
 
 impl Compression {
-    fn from_reader( input: &mut Read, bpp: u32 ) -> Result<Option<Compression>> {
+    fn from_reader( input: &mut Read, version: Version, bpp: u32 ) -> Result<Option<Compression>> {
+        let os2 = matches!( version, Version::Os2( _ ) );
+
         match input.read_u32::<LittleEndian>()? {
-            0x00 => Ok( None ),
+            0x00 if bpp != 0 => Ok( None ),
             0x01 if bpp == 8 => Ok( Some( Compression::RunLength8 ) ),
             0x02 if bpp == 4 => Ok( Some( Compression::RunLength4 ) ),
+            0x03 if bpp == 1 && os2 => Ok( Some( Compression::Huffman1D ) ),
+            0x04 if bpp == 24 && os2 => Ok( Some( Compression::RunLength24 ) ),
+            0x04 if !os2 => Ok( Some( Compression::Jpeg ) ),
+            0x05 => Ok( Some( Compression::Png ) ),
+            0x06 if bpp == 16 || bpp == 32 => Ok( Some( Compression::AlphaBitmask ) ),
+            0x0B if ( bpp != 0 && bpp <= 8 ) || bpp == 32 => Ok( Some( Compression::Cmyk ) ),
+            0x0C if bpp == 8 => Ok( Some( Compression::CmykRunLength8 ) ),
+            0x0D if bpp == 4 => Ok( Some( Compression::CmykRunLength4 ) ),
             0x03 if bpp == 16 || bpp == 32 => Ok( Some( Compression::Bitmask ) ),
             x @ _ => Err( new_data_error(
                 format!( "Invalid compression 0x{:X} for {}-bit", x, bpp ) ) ),
@@ -133,6 +312,35 @@ impl Compression {
     }
 }
 
+impl DeviceHeader {
+    fn from_reader( input: &mut Read ) -> Result<DeviceHeader> {
+        let width = input.read_u16::<LittleEndian>()? as u32;
+        let height = input.read_u16::<LittleEndian>()? as u32;
+        let byte_width = input.read_u16::<LittleEndian>()? as u32;
+        let planes = input.read_u8()? as u32;
+        let bpp = input.read_u8()? as u32;
+
+        match ( planes, bpp ) {
+            ( 1, 1 ) | ( 3, 1 ) | ( 4, 1 ) | ( 1, 4 ) => {},
+            _ => return Err( new_data_error( format!(
+                "Invalid device dependent bitmap with {} planes of {} bits", planes, bpp ) ) ),
+        }
+
+        if byte_width < ( width * bpp ).div_ceil( 8 ) {
+            return Err( new_data_error( format!(
+                "Invalid byte width {} for image width {}", byte_width, width ) ) );
+        }
+
+        Ok( DeviceHeader {
+            width,
+            height,
+            byte_width,
+            planes,
+            bpp,
+        } )
+    }
+}
+
 impl CoreHeader {
     fn from_reader( input: &mut Read, version: Version ) -> Result<CoreHeader> {
         let ( width, height ) = match version {
@@ -163,7 +371,7 @@ impl CoreHeader {
 
         let bpp = match input.read_u16::<LittleEndian>()? as u32 {
             x @ 1 | x @ 4 | x @ 8 | x @ 24 => x,
-            x @ 16 | x @ 32 if version != Version::Microsoft2 => x,
+            x @ 0 | x @ 2 | x @ 16 | x @ 32 | x @ 64 if version != Version::Microsoft2 => x,
             x @ _ => return Err( new_data_error( format!( "Invalid bits per pixel {}", x ) ) ),
         };
 
@@ -197,6 +405,37 @@ impl InfoHeader {
     }
 }
 
+impl Os2Header {
+    fn from_reader( input: &mut Read ) -> Result<Os2Header> {
+        let units = input.read_u16::<LittleEndian>()?;
+        input.read_u16::<LittleEndian>()?; // Reserved
+        let recording = input.read_u16::<LittleEndian>()?;
+        let rendering = input.read_u16::<LittleEndian>()?;
+        let size1 = input.read_u32::<LittleEndian>()?;
+        let size2 = input.read_u32::<LittleEndian>()?;
+        let color_encoding = input.read_u32::<LittleEndian>()?;
+        let identifier = input.read_u32::<LittleEndian>()?;
+
+        if recording != 0 {
+            return Err( new_data_error(
+                format!( "Invalid recording algorithm {}", recording ) ) );
+        }
+
+        if color_encoding != 0 {
+            return Err( new_data_error(
+                format!( "Invalid color encoding {}", color_encoding ) ) );
+        }
+
+        Ok( Os2Header {
+            units,
+            rendering,
+            size1,
+            size2,
+            identifier,
+        } )
+    }
+}
+
 impl BitfieldMask {
     fn new() -> BitfieldMask {
         BitfieldMask {
@@ -225,14 +464,14 @@ impl BitfieldMask {
         }
     }
 
-    fn from_reader( input: &mut Read, version: Version ) -> Result<BitfieldMask> {
+    fn from_reader( input: &mut Read, with_alpha: bool ) -> Result<BitfieldMask> {
         let red = input.read_u32::<LittleEndian>()?;
         let green = input.read_u32::<LittleEndian>()?;
         let blue = input.read_u32::<LittleEndian>()?;
 
-        let alpha = match version {
-            Version::Microsoft4 | Version::Microsoft5 => input.read_u32::<LittleEndian>()?,
-            _ => 0x00,
+        let alpha = match with_alpha {
+            true => input.read_u32::<LittleEndian>()?,
+            false => 0x00,
         };
 
         Ok( BitfieldMask { red, green, blue, alpha } )
@@ -289,9 +528,22 @@ impl ProfileHeader {
     }
 }
 
-fn read_palette( input: &mut Read, version: Version, bpp: u32, used_colors: u32 ) -> Result<Vec<u8>> {
+// Converts a CMYK color into RGB
+fn cmyk_to_rgb( c: u8, m: u8, y: u8, k: u8 ) -> ( u8, u8, u8 ) {
+    let white = 255 - k as u32;
+
+    (
+        ( ( 255 - c as u32 ) * white / 255 ) as u8,
+        ( ( 255 - m as u32 ) * white / 255 ) as u8,
+        ( ( 255 - y as u32 ) * white / 255 ) as u8,
+    )
+}
+
+fn read_palette( input: &mut Read, version: Version, bpp: u32, used_colors: u32, cmyk: bool )
+    -> Result<Vec<u8>> {
+
     let size = match used_colors {
-        0 if bpp < 16 => ( 1 << bpp ) as usize,
+        0 if bpp != 0 && bpp < 16 => ( 1 << bpp ) as usize,
         _ => used_colors as usize,
     };
     if size > 0 {
@@ -305,6 +557,22 @@ fn read_palette( input: &mut Read, version: Version, bpp: u32, used_colors: u32
                 }
                 colors
             },
+            _ if cmyk => {
+                let mut colors = Vec::with_capacity( size * 4 );
+                for _ in 0..size {
+                    let k = input.read_u8()?;
+                    let y = input.read_u8()?;
+                    let m = input.read_u8()?;
+                    let c = input.read_u8()?;
+                    let ( r, g, b ) = cmyk_to_rgb( c, m, y, k );
+
+                    colors.push( b );
+                    colors.push( g );
+                    colors.push( r );
+                    colors.push( 255 ); // a
+                }
+                colors
+            },
             _ => {
                 let mut colors = Vec::with_capacity( size * 3 );
                 for x in 0..size {
@@ -323,17 +591,35 @@ fn read_palette( input: &mut Read, version: Version, bpp: u32, used_colors: u32
     }
 }
 
-fn read_file_header( input: &mut Read ) -> Result<FileType> {
-    let file_type = FileType::from_reader( input )?;
-    let _file_size = input.read_u32::<LittleEndian>()?;
-    // TODO: make sense of file_size (error when too big or small)
+impl FileHeader {
+    pub( crate ) fn from_reader( input: &mut Read, file_type: FileType ) -> Result<FileHeader> {
+        let file_size = input.read_u32::<LittleEndian>()?;
+        // TODO: make sense of file_size (error when too big or small)
 
-    input.read_u32::<LittleEndian>()?; // Reserved
+        // Reserved for bitmaps, but icons and pointers store their hotspot here
+        let hotspot_x = input.read_i16::<LittleEndian>()?;
+        let hotspot_y = input.read_i16::<LittleEndian>()?;
 
-    let _data_offset = input.read_u32::<LittleEndian>()?;
-    // TODO: make sense of data_offset (error when too big or small)
+        let data_offset = input.read_u32::<LittleEndian>()?;
+        // TODO: make sense of data_offset (error when too big or small)
+
+        Ok( FileHeader {
+            file_type,
+            file_size,
+            hotspot_x,
+            hotspot_y,
+            data_offset,
+        } )
+    }
+}
 
-    Ok( file_type )
+pub( crate ) fn read_file_header( input: &mut Read ) -> Result<FileHeader> {
+    match FileType::from_reader( input )? {
+        FileType::DeviceDependentBitmap => Err( new_data_error(
+            "Device dependent bitmaps have no file header" ) ),
+        FileType::Riff => Err( new_data_error( "RIFF containers have no file header" ) ),
+        file_type => FileHeader::from_reader( input, file_type ),
+    }
 }
 
 fn read_bitmask( input: &mut Read, version: Version, compression: Option<Compression>, bpp: u32 )
@@ -341,11 +627,15 @@ fn read_bitmask( input: &mut Read, version: Version, compression: Option<Compres
 
     match version {
         Version::Microsoft3 if compression == Some( Compression::Bitmask )
-            => BitfieldMask::from_reader( input, version ),
-        Version::Microsoft3 if compression == None
+            => BitfieldMask::from_reader( input, false ),
+        Version::Microsoft3 if compression == Some( Compression::AlphaBitmask )
+            => BitfieldMask::from_reader( input, true ),
+        Version::Microsoft3 | Version::Os2( _ ) if compression == None
             => Ok( BitfieldMask::from_bpp( bpp ) ),
-        Version::Microsoft4 | Version::Microsoft5
-            => BitfieldMask::from_reader( input, version ),
+        Version::Adobe2
+            => BitfieldMask::from_reader( input, false ),
+        Version::Adobe3 | Version::Microsoft4 | Version::Microsoft5
+            => BitfieldMask::from_reader( input, true ),
         _ => Ok( BitfieldMask::new() ),
     }
 }
@@ -354,44 +644,191 @@ pub( crate ) fn decode<TBuilder: super::Builder>(
     input: &mut Read, mut builder: TBuilder ) -> Result<TBuilder> {
 
     // Read file header
-    let file_type = read_file_header( input )?;
+    let file_type = FileType::from_reader( input )?;
+
+    match file_type {
+        FileType::DeviceDependentBitmap => decode_device( input, builder ),
+        FileType::Riff => riff::decode( input, builder ),
+        FileType::DeviceIndependentBitmap => {
+            let file = FileHeader::from_reader( input, file_type )?;
+
+            let header = read_header( input )?;
+            skip_to( input, FILE_HEADER_SIZE + header.size() as u64, file.data_offset as u64 )?;
+            decode_pixels( input, &header, file.data_offset as u64, &mut builder )?;
+
+            Ok( builder )
+        },
+        FileType::BitmapArray | FileType::ColorIcon | FileType::ColorPointer | FileType::Icon
+            | FileType::Pointer => Err( new_unsupported_error( "decode", file_type ) ),
+    }
+}
+
+// Decodes a packed bitmap that starts with its info header. Bitfield masks and palette follow
+// the header just like in a bitmap file, but the pixel data has to follow the palette directly.
+pub( crate ) fn decode_dib<TBuilder: super::Builder>(
+    input: &mut Read, mut builder: TBuilder ) -> Result<TBuilder> {
+
+    let header = read_header( input )?;
+    let offset = header.size() as u64;
+    decode_pixels( input, &header, offset, &mut builder )?;
+
+    Ok( builder )
+}
+
+// Skips the gap some writers leave between the headers and the pixel data
+pub( crate ) fn skip_to( input: &mut Read, position: u64, offset: u64 ) -> Result<()> {
+    if offset > position {
+        io::copy( &mut input.take( offset - position ), &mut io::sink() )?;
+    }
+
+    Ok( () )
+}
+
+// Huffman 1D and RLE24 compression are only used by OS/2 and share their values with bitfields
+// and JPEG, while Adobe headers are only used for bitfields. The buffer holds the fields that
+// follow the header size.
+fn is_truncated_os2( version: Version, buffer: &[u8] ) -> bool {
+    let bpp = LittleEndian::read_u16( &buffer[ 10..12 ] );
+    let compression = LittleEndian::read_u32( &buffer[ 12..16 ] );
+
+    let os2 = ( compression == 3 && bpp == 1 ) || ( compression == 4 && bpp == 24 );
+    let bitfields = compression == 3 || compression == 6 || bpp == 16 || bpp == 32;
+
+    match version {
+        Version::Microsoft3 => os2,
+        Version::Adobe2 | Version::Adobe3 => os2 || !bitfields,
+        _ => false,
+    }
+}
 
+pub( crate ) fn read_header( input: &mut Read ) -> Result<Header> {
     // Read core header
-    let version = Version::from_reader( input )?;
-    let core = CoreHeader::from_reader( input, version )?;
+    let mut version = Version::from_reader( input )?;
+
+    // OS/2 2.x headers may be truncated at any field, so they are read into a zero filled
+    // buffer first and the missing fields default to zero. Truncated headers can have the size
+    // of other headers, which are told apart by their bits per pixel and compression.
+    let mut buffer = [0; 0x3C];
+    let buffered = match version {
+        Version::Os2( _ ) | Version::Microsoft3 | Version::Adobe2 | Version::Adobe3
+            => version.size() as usize - 4,
+        _ => 0,
+    };
+    input.read_exact( &mut buffer[ ..buffered ] )?;
+
+    if buffered > 0 && is_truncated_os2( version, &buffer ) {
+        version = Version::Os2( version.size() );
+    }
+
+    let length = match version {
+        Version::Os2( _ ) => buffer.len(),
+        _ => buffered,
+    };
+    let mut chain = ( &buffer[ ..length ] ).chain( &mut *input );
+    let header: &mut Read = &mut chain;
+
+    let core = CoreHeader::from_reader( header, version )?;
 
     // Read info header
     let ( compression, info, bitmask ) = match version {
         Version::Microsoft2 => ( None, None, BitfieldMask::new() ),
         _ => {
-            let compression = Compression::from_reader( input, core.bpp )?;
-            let info = InfoHeader::from_reader( input, compression )?;
-            let bitmask = read_bitmask( input, version, compression, core.bpp )?;
+            let compression = Compression::from_reader( header, version, core.bpp )?;
+            let info = InfoHeader::from_reader( header, compression )?;
+            let bitmask = read_bitmask( header, version, compression, core.bpp )?;
 
             ( compression, Some( info ), bitmask )
         },
     };
 
+    // Read OS/2 header
+    let os2 = match version {
+        Version::Os2( _ ) => Some( Os2Header::from_reader( header )? ),
+        _ => None,
+    };
+
     // Read extra header
     let extra = match version {
         Version::Microsoft4 | Version::Microsoft5
-            => Some( ExtraHeader::from_reader( input )? ),
+            => Some( ExtraHeader::from_reader( header )? ),
         _ => None,
     };
 
     // Read profile header
     let profile = match core.version {
         Version::Microsoft5
-            => Some( ProfileHeader::from_reader( input )? ),
+            => Some( ProfileHeader::from_reader( header )? ),
         _ => None,
     };
 
     // Read palette
+    let cmyk = matches!( compression, Some( Compression::Cmyk )
+        | Some( Compression::CmykRunLength8 )
+        | Some( Compression::CmykRunLength4 ) );
     let palette = match info {
-        Some( ref i ) => read_palette( input, version, core.bpp, i.used_colors )?,
-        None => read_palette( input, version, core.bpp, 0 )?,
+        Some( ref i ) => read_palette( input, version, core.bpp, i.used_colors, cmyk )?,
+        None => read_palette( input, version, core.bpp, 0, cmyk )?,
+    };
+
+    Ok( Header {
+        version,
+        core,
+        info,
+        bitmask,
+        os2,
+        extra,
+        profile,
+        palette,
+    } )
+}
+
+// Reads the JPEG or PNG stream of a bitmap that uses embedded compression
+pub( crate ) fn read_embedded( input: &mut Read, header: &Header, offset: u64 )
+    -> Result<Option<Embedded>> {
+
+    let ( kind, length ) = match header.info {
+        Some( InfoHeader { compression: Some( Compression::Jpeg ), image_size, .. } )
+            => ( EmbeddedKind::Jpeg, image_size ),
+        Some( InfoHeader { compression: Some( Compression::Png ), image_size, .. } )
+            => ( EmbeddedKind::Png, image_size ),
+        _ => return Ok( None ),
     };
 
+    // The image size may be zero in which case the stream makes up the rest of the input
+    let mut data = Vec::new();
+    match length {
+        0 => { input.read_to_end( &mut data )?; },
+        _ => { input.take( length as u64 ).read_to_end( &mut data )?; },
+    }
+
+    if length != 0 && data.len() != length as usize {
+        return Err( new_data_error( "Unexpected end of embedded image stream" ) );
+    }
+
+    Ok( Some( Embedded {
+        kind,
+        offset,
+        length: data.len() as u64,
+        data,
+    } ) )
+}
+
+// Decodes the pixel data located at the current position. The offset is the position of the
+// pixel data within the file and is only used to describe embedded image streams.
+pub( crate ) fn decode_pixels<TBuilder: super::Builder>(
+    input: &mut Read, header: &Header, offset: u64, builder: &mut TBuilder ) -> Result<()> {
+
+    if let Some( embedded ) = read_embedded( input, header, offset )? {
+        builder.set_size( header.core.width, header.core.height );
+        return builder.set_embedded( &embedded );
+    }
+
+    let version = header.version;
+    let core = &header.core;
+    let info = &header.info;
+    let palette = &header.palette;
+    let bitmask = &header.bitmask;
+
     // TODO: REWORK EVERYTHING FROM HERE ON
     // Set output size
     builder.set_size( core.width, core.height );
@@ -407,27 +844,53 @@ pub( crate ) fn decode<TBuilder: super::Builder>(
         _ => 4,
     };
 
-    let compression = match version {
-        Version::Microsoft2 => false,
-        _ if match info {
-            Some( ref i ) => if i.compression.is_some() { true } else { false },
-            None => false,
-        } => true,
-        _ => false,
+    let compression = match *info {
+        Some( ref i ) => i.compression,
+        None => None,
     };
 
     let decode_row = match bpp {
         1 => decode_1bpp::<TBuilder>,
-        4 if !compression => decode_4bpp::<TBuilder>,
-        8 if !compression => decode_8bpp::<TBuilder>,
+        2 => decode_2bpp::<TBuilder>,
+        4 => decode_4bpp::<TBuilder>,
+        8 => decode_8bpp::<TBuilder>,
         16 => decode_16bpp::<TBuilder>,
         24 => decode_24bpp::<TBuilder>,
+        32 if compression == Some( Compression::Cmyk ) => decode_32bpp_cmyk::<TBuilder>,
         32 => decode_32bpp::<TBuilder>,
+        64 => decode_64bpp::<TBuilder>,
         _ => decode_nothing::<TBuilder>,
     };
 
-    if bpp == 8 && compression {
-        let count = info.unwrap().image_size as usize;
+    if compression == Some( Compression::Huffman1D ) {
+        let count = info.as_ref().unwrap().image_size as usize;
+        if count == 0 {
+            return Err( new_data_error(
+                "Image size in bytes can't be null when using Huffman 1D compression" ) );
+        }
+        let mut buffer = vec![0; count];
+        input.read_exact( &mut buffer )?;
+
+        let mut decoder = huffman::Decoder::new( &buffer );
+        let mut indices = vec![0; width as usize];
+
+        for y in 0..height {
+            decoder.read_row( &mut indices )?;
+
+            let row = if !core.top_down { height - y - 1 } else { y };
+
+            for ( x, index ) in indices.iter().enumerate() {
+                let b = palette[ color_width * *index as usize ];
+                let g = palette[ ( color_width * *index as usize ) + 1 ];
+                let r = palette[ ( color_width * *index as usize ) + 2 ];
+
+                builder.set_pixel( x as u32, row, r, g, b, 255 );
+            }
+        }
+
+    } else if compression == Some( Compression::RunLength8 )
+        || compression == Some( Compression::CmykRunLength8 ) {
+        let count = info.as_ref().unwrap().image_size as usize;
         if count == 0 {
             panic!( "Image size in bytes can't be null when using RLE8 compression" );
         }
@@ -507,8 +970,115 @@ pub( crate ) fn decode<TBuilder: super::Builder>(
             }
         }
 
-    } else if bpp == 4 && compression {
-        let count = info.unwrap().image_size as usize;
+    } else if compression == Some( Compression::RunLength24 ) {
+        let count = info.as_ref().unwrap().image_size as usize;
+        if count == 0 {
+            return Err( new_data_error(
+                "Image size in bytes can't be null when using RLE24 compression" ) );
+        }
+        let mut buffer = vec![0; count];
+        input.read_exact( &mut buffer )?;
+        let buffer = buffer;
+
+        let mut x: u32 = 0;
+        let mut y: u32 = if !core.top_down { height - 1 } else { 0 };
+        let mut index: usize = 0;
+        let row_mod: i32 = if !core.top_down { -1 } else { 1 };
+
+        // Decoding stops when the data moves past the last row
+        'decode: loop {
+            if index + 1 >= count {
+                break;
+            }
+
+            let first = buffer[ index ] as usize;
+            let second = buffer[ index + 1 ] as usize;
+
+            if first == 0 {
+                index += 2;
+
+                if second == 0 {
+                    x = 0;
+                    y = match y.checked_add_signed( row_mod ) {
+                        Some( y ) => y,
+                        None => break,
+                    };
+
+                } else if second == 1 {
+                    break;
+
+                } else if second == 2 {
+                    if index + 1 >= count {
+                        break;
+                    }
+
+                    let dx = buffer[ index ] as u32;
+                    let dy = buffer[ index + 1 ] as i32 * row_mod;
+                    index += 2;
+
+                    x += dx;
+                    y = match y.checked_add_signed( dy ) {
+                        Some( y ) => y,
+                        None => break,
+                    };
+
+                } else {
+                    for _ in 0..second {
+                        if index + 2 >= count {
+                            break;
+                        }
+
+                        if x >= width {
+                            x = 0;
+                            y = match y.checked_add_signed( row_mod ) {
+                                Some( y ) => y,
+                                None => break 'decode,
+                            };
+                        }
+
+                        let b = buffer[ index ];
+                        let g = buffer[ index + 1 ];
+                        let r = buffer[ index + 2 ];
+
+                        builder.set_pixel( x, y, r, g, b, 255 );
+                        x += 1;
+
+                        index += 3;
+                    }
+                    index += match ( second * 3 ) % 2 {
+                        0 => 0,
+                        _ => 1,
+                    };
+                }
+
+            } else {
+                if index + 3 >= count {
+                    break;
+                }
+
+                let b = buffer[ index + 1 ];
+                let g = buffer[ index + 2 ];
+                let r = buffer[ index + 3 ];
+                index += 4;
+
+                for _ in 0..first {
+                    if x >= width {
+                        x = 0;
+                        y = match y.checked_add_signed( row_mod ) {
+                            Some( y ) => y,
+                            None => break 'decode,
+                        };
+                    }
+
+                    builder.set_pixel( x, y, r, g, b, 255 );
+                    x += 1;
+                }
+            }
+        }
+
+    } else if compression == Some( Compression::RunLength4 )
+        || compression == Some( Compression::CmykRunLength4 ) {
+        let count = info.as_ref().unwrap().image_size as usize;
         if count == 0 {
             panic!( "Image size in bytes can't be null when using RLE4 compression" );
         }
@@ -641,13 +1211,98 @@ pub( crate ) fn decode<TBuilder: super::Builder>(
 
             let row = if !core.top_down { height - y - 1 } else { y };
 
-            decode_row( width, row, &buffer, version, &palette, &bitmask, &mut builder );
+            decode_row( width, row, &buffer, version, palette, bitmask, builder );
+        }
+    }
+
+    Ok( () )
+}
+
+// Standard colors of 4-bit IRGB display devices
+const DEVICE_COLORS: [( u8, u8, u8 ); 16] = [
+    ( 0x00, 0x00, 0x00 ),
+    ( 0x00, 0x00, 0x80 ),
+    ( 0x00, 0x80, 0x00 ),
+    ( 0x00, 0x80, 0x80 ),
+    ( 0x80, 0x00, 0x00 ),
+    ( 0x80, 0x00, 0x80 ),
+    ( 0x80, 0x80, 0x00 ),
+    ( 0xC0, 0xC0, 0xC0 ),
+    ( 0x80, 0x80, 0x80 ),
+    ( 0x00, 0x00, 0xFF ),
+    ( 0x00, 0xFF, 0x00 ),
+    ( 0x00, 0xFF, 0xFF ),
+    ( 0xFF, 0x00, 0x00 ),
+    ( 0xFF, 0x00, 0xFF ),
+    ( 0xFF, 0xFF, 0x00 ),
+    ( 0xFF, 0xFF, 0xFF ),
+];
+
+fn device_color( bits: u32, index: u32 ) -> ( u8, u8, u8 ) {
+    let full = |bit: u32| if index & bit != 0 { 0xFF } else { 0x00 };
+
+    match bits {
+        1 => ( full( 1 ), full( 1 ), full( 1 ) ),
+        3 => ( full( 4 ), full( 2 ), full( 1 ) ),
+        _ => DEVICE_COLORS[ index as usize ],
+    }
+}
+
+// Decodes a Windows 1.x device dependent bitmap. Rows are stored top-down and every plane
+// follows the previous one, each contributing the next higher bits of a pixel's color index.
+fn decode_device<TBuilder: super::Builder>(
+    input: &mut Read, mut builder: TBuilder ) -> Result<TBuilder> {
+
+    let header = DeviceHeader::from_reader( input )?;
+    let plane_size = ( header.byte_width * header.height ) as usize;
+    let mut buffer = vec![0; plane_size * header.planes as usize];
+    input.read_exact( &mut buffer )?;
+
+    builder.set_size( header.width, header.height );
+
+    let mask = ( 1 << header.bpp ) - 1;
+    for y in 0..header.height {
+        for x in 0..header.width {
+            let bit = x * header.bpp;
+            let mut index = 0;
+
+            for plane in 0..header.planes {
+                let offset = plane as usize * plane_size
+                    + ( y * header.byte_width + bit / 8 ) as usize;
+                let value = ( buffer[ offset ] as u32 >> ( 8 - header.bpp - bit % 8 ) ) & mask;
+
+                index |= value << ( plane * header.bpp );
+            }
+
+            let ( r, g, b ) = device_color( header.planes * header.bpp, index );
+            builder.set_pixel( x, y, r, g, b, 255 );
         }
     }
 
     Ok( builder )
 }
 
+// Decodes an uncompressed 1-bit mask where every pixel is set to its bit value instead of a color
+pub( crate ) fn decode_mask<TBuilder: super::Builder>(
+    input: &mut Read, width: u32, height: u32, top_down: bool, builder: &mut TBuilder ) -> Result<()> {
+
+    let palette = [ 0, 0, 0, 255, 1, 1, 1, 255 ];
+    let size = width.div_ceil( 32 ) * 4;
+    let mut buffer = vec![0; size as usize];
+
+    builder.set_size( width, height );
+
+    for y in 0..height {
+        input.read_exact( &mut buffer )?;
+
+        let row = if !top_down { height - y - 1 } else { y };
+
+        decode_1bpp( width, row, &buffer, Version::Microsoft3, &palette, &BitfieldMask::new(), builder );
+    }
+
+    Ok( () )
+}
+
 fn decode_1bpp<TBuilder: super::Builder>(
     width: u32, row: u32, buf: &[u8], version: Version, palette: &[u8], mask: &BitfieldMask, builder: &mut TBuilder ) {
 
@@ -674,6 +1329,34 @@ fn decode_1bpp<TBuilder: super::Builder>(
     }
 }
 
+fn decode_2bpp<TBuilder: super::Builder>(
+    width: u32, row: u32, buf: &[u8], version: Version, palette: &[u8], _mask: &BitfieldMask, builder: &mut TBuilder ) {
+
+    let mut x: u32 = 0;
+    let color_width = match version {
+        Version::Microsoft2 => 3,
+        _ => 4,
+    };
+
+    for byte in buf {
+        for shift in [ 6, 4, 2, 0 ].iter() {
+            let index = color_width * ( ( *byte >> shift ) & 0x03 ) as usize;
+
+            // Indices beyond a palette with fewer used colors are drawn black
+            let ( r, g, b ) = match palette.get( index..index + 3 ) {
+                Some( color ) => ( color[ 2 ], color[ 1 ], color[ 0 ] ),
+                None => ( 0, 0, 0 ),
+            };
+            builder.set_pixel( x, row, r, g, b, 255 );
+
+            x += 1;
+            if x >= width {
+                return;
+            }
+        }
+    }
+}
+
 fn decode_4bpp<TBuilder: super::Builder>(
     width: u32, row: u32, buf: &[u8], version: Version, palette: &[u8], mask: &BitfieldMask, builder: &mut TBuilder ) {
 
@@ -800,7 +1483,7 @@ fn decode_32bpp<TBuilder: super::Builder>(
         _ => 4,
     };
 
-    let alpha_shift = mask.red.trailing_zeros();
+    let alpha_shift = mask.alpha.trailing_zeros();
     let red_shift = mask.red.trailing_zeros();
     let green_shift = mask.green.trailing_zeros();
     let blue_shift = mask.blue.trailing_zeros();
@@ -828,7 +1511,155 @@ fn decode_32bpp<TBuilder: super::Builder>(
     }
 }
 
+fn decode_32bpp_cmyk<TBuilder: super::Builder>(
+    width: u32, row: u32, buf: &[u8], _version: Version, _palette: &[u8], _mask: &BitfieldMask, builder: &mut TBuilder ) {
+
+    let mut x: u32 = 0;
+
+    for bytes in buf.chunks( 4 ) {
+        let ( r, g, b ) = cmyk_to_rgb( bytes[3], bytes[2], bytes[1], bytes[0] );
+        builder.set_pixel( x, row, r, g, b, 255 );
+
+        x += 1;
+        if x >= width {
+            break;
+        }
+    }
+}
+
+// Pixels are stored as blue, green, red and alpha channels of linear light in s2.13 fixed-point
+fn decode_64bpp<TBuilder: super::Builder>(
+    width: u32, row: u32, buf: &[u8], _version: Version, _palette: &[u8], _mask: &BitfieldMask, builder: &mut TBuilder ) {
+
+    let mut x: u32 = 0;
+
+    for mut bytes in buf.chunks( 8 ) {
+        let b = bytes.read_i16::<LittleEndian>().unwrap() as f32 / 8192.0;
+        let g = bytes.read_i16::<LittleEndian>().unwrap() as f32 / 8192.0;
+        let r = bytes.read_i16::<LittleEndian>().unwrap() as f32 / 8192.0;
+        let a = bytes.read_i16::<LittleEndian>().unwrap() as f32 / 8192.0;
+
+        builder.set_pixel_linear( x, row, r, g, b, a );
+
+        x += 1;
+        if x >= width {
+            break;
+        }
+    }
+}
+
 fn decode_nothing<TBuilder: super::Builder>(
     _: u32, _: u32, _: &[u8], _: Version, _: &[u8], _: &BitfieldMask, _: &mut TBuilder ) {
     // no-op
 }
+
+#[cfg(test)]
+pub( crate ) mod tests {
+    use byteorder::{
+        WriteBytesExt,
+        LittleEndian,
+    };
+
+    use canvas::Canvas;
+    use super::decode;
+
+    // Builds a bitmap file whose header holds the fields of a Version 3 header up to the number
+    // of used colors, and is truncated or zero padded to the given size. Palette entries take
+    // four bytes each.
+    pub( crate ) fn bitmap( header_size: u32, width: i32, height: i32, bpp: u16, compression: u32,
+        palette: &[u8], data: &[u8] ) -> Vec<u8> {
+
+        let mut header = Vec::new();
+        header.write_u32::<LittleEndian>( header_size ).unwrap();
+        header.write_i32::<LittleEndian>( width ).unwrap();
+        header.write_i32::<LittleEndian>( height ).unwrap();
+        header.write_u16::<LittleEndian>( 1 ).unwrap();
+        header.write_u16::<LittleEndian>( bpp ).unwrap();
+        header.write_u32::<LittleEndian>( compression ).unwrap();
+        header.write_u32::<LittleEndian>( data.len() as u32 ).unwrap();
+        header.write_i32::<LittleEndian>( 0 ).unwrap();
+        header.write_i32::<LittleEndian>( 0 ).unwrap();
+        header.write_u32::<LittleEndian>( palette.len() as u32 / 4 ).unwrap();
+        header.resize( header_size as usize, 0 );
+
+        let offset = 14 + header.len() + palette.len();
+        let mut file = b"BM".to_vec();
+        file.write_u32::<LittleEndian>( ( offset + data.len() ) as u32 ).unwrap();
+        file.write_u32::<LittleEndian>( 0 ).unwrap();
+        file.write_u32::<LittleEndian>( offset as u32 ).unwrap();
+        file.extend_from_slice( &header );
+        file.extend_from_slice( palette );
+        file.extend_from_slice( data );
+
+        file
+    }
+
+    #[test]
+    fn truncated_os2_header_with_huffman_1d() {
+        // White 1, black 1
+        let file = bitmap( 0x28, 2, 1, 1, 3, &[ 255, 255, 255, 0, 0, 0, 0, 0 ], &[ 0x1D, 0x00 ] );
+        let canvas = decode( &mut &file[ .. ], Canvas::new() ).unwrap();
+
+        assert_eq!( canvas.pixel( 0, 0 ), ( 255, 255, 255, 255 ) );
+        assert_eq!( canvas.pixel( 1, 0 ), ( 0, 0, 0, 255 ) );
+    }
+
+    #[test]
+    fn truncated_os2_header_with_rle24() {
+        // Run of two pixels, end of bitmap
+        let file = bitmap( 0x28, 2, 1, 24, 4, &[], &[ 2, 0x30, 0x20, 0x10, 0, 1 ] );
+        let canvas = decode( &mut &file[ .. ], Canvas::new() ).unwrap();
+
+        assert_eq!( canvas.pixel( 0, 0 ), ( 0x10, 0x20, 0x30, 255 ) );
+        assert_eq!( canvas.pixel( 1, 0 ), ( 0x10, 0x20, 0x30, 255 ) );
+    }
+
+    #[test]
+    fn rle24_escapes() {
+        // Red run on the bottom row, end of line, delta of one pixel, blue run, end of bitmap
+        let data = [ 2, 0, 0, 255, 0, 0, 0, 2, 1, 0, 1, 255, 0, 0, 0, 1 ];
+        let file = bitmap( 0x40, 2, 2, 24, 4, &[], &data );
+        let canvas = decode( &mut &file[ .. ], Canvas::new() ).unwrap();
+
+        assert_eq!( canvas.pixel( 0, 0 ), ( 0, 0, 0, 0 ) );
+        assert_eq!( canvas.pixel( 1, 0 ), ( 0, 0, 255, 255 ) );
+        assert_eq!( canvas.pixel( 0, 1 ), ( 255, 0, 0, 255 ) );
+        assert_eq!( canvas.pixel( 1, 1 ), ( 255, 0, 0, 255 ) );
+    }
+
+    #[test]
+    fn rle24_stops_past_last_row() {
+        // A green pixel followed by an end of line, a delta or a run that moves past the last row
+        let streams: [&[u8]; 3] = [
+            &[ 1, 0, 255, 0, 0, 0, 1, 255, 0, 0, 0, 1 ],
+            &[ 1, 0, 255, 0, 0, 2, 0, 255, 1, 255, 0, 0, 0, 1 ],
+            &[ 1, 0, 255, 0, 2, 255, 0, 0, 0, 1 ],
+        ];
+
+        for data in streams.iter() {
+            let file = bitmap( 0x40, 1, 1, 24, 4, &[], data );
+            let canvas = decode( &mut &file[ .. ], Canvas::new() ).unwrap();
+
+            assert_eq!( canvas.pixel( 0, 0 ), ( 0, 255, 0, 255 ) );
+        }
+    }
+
+    #[test]
+    fn two_bits_per_pixel() {
+        // Indices 1 and 3 within a palette of two used colors
+        let file = bitmap( 0x28, 2, 1, 2, 0, &[ 0, 0, 255, 0, 255, 0, 0, 0 ], &[ 0x70, 0, 0, 0 ] );
+        let canvas = decode( &mut &file[ .. ], Canvas::new() ).unwrap();
+
+        assert_eq!( canvas.pixel( 0, 0 ), ( 0, 0, 255, 255 ) );
+        assert_eq!( canvas.pixel( 1, 0 ), ( 0, 0, 0, 255 ) );
+    }
+
+    #[test]
+    fn unsupported_file_types_name_their_function() {
+        let error = decode( &mut &b"BA\0\0\0\0"[ .. ], Canvas::new() ).err().unwrap();
+        assert_eq!( error.to_string(), "decode doesn't handle bitmap arrays, use bmp_rs::read_array" );
+
+        let error = decode( &mut &b"CI\0\0\0\0"[ .. ], Canvas::new() ).err().unwrap();
+        assert_eq!( error.to_string(), "decode doesn't handle icons and pointers, use bmp_rs::decode_pointer" );
+    }
+}
@@ -0,0 +1,70 @@
+use std::io::Result;
+
+use bitmap::new_data_error;
+
+// An in-memory RGBA image used to combine several decoded bitmaps into one
+pub( crate ) struct Canvas {
+    pub( crate ) width: u32,
+    pub( crate ) height: u32,
+    pixels: Vec<u8>,
+}
+
+impl Canvas {
+    pub( crate ) fn new() -> Canvas {
+        Canvas {
+            width: 0,
+            height: 0,
+            pixels: Vec::new(),
+        }
+    }
+
+    // Checks that an image of the given size can be held by a canvas, before anything is decoded
+    pub( crate ) fn check_size( width: u32, height: u32 ) -> Result<()> {
+        match width.checked_mul( height ).and_then( |pixels| pixels.checked_mul( 4 ) ) {
+            Some( _ ) => Ok( () ),
+            None => Err( new_data_error( format!( "Image size {}x{} is too large", width, height ) ) ),
+        }
+    }
+
+    fn index( &self, x: u32, y: u32 ) -> usize {
+        ( y as usize * self.width as usize + x as usize ) * 4
+    }
+
+    pub( crate ) fn pixel( &self, x: u32, y: u32 ) -> ( u8, u8, u8, u8 ) {
+        let index = self.index( x, y );
+
+        (
+            self.pixels[ index ],
+            self.pixels[ index + 1 ],
+            self.pixels[ index + 2 ],
+            self.pixels[ index + 3 ],
+        )
+    }
+}
+
+impl super::Builder for Canvas {
+    type TResult = ();
+
+    fn set_size( &mut self, width: u32, height: u32 ) {
+        self.width = width;
+        self.height = height;
+        self.pixels = vec![0; width as usize * height as usize * 4];
+    }
+
+    fn set_pixel( &mut self, x: u32, y: u32, r: u8, g: u8, b: u8, a: u8 ) {
+        // Run length encoded data may point outside of the image
+        if x >= self.width || y >= self.height {
+            return;
+        }
+
+        let index = self.index( x, y );
+        self.pixels[ index ] = r;
+        self.pixels[ index + 1 ] = g;
+        self.pixels[ index + 2 ] = b;
+        self.pixels[ index + 3 ] = a;
+    }
+
+    fn build( &mut self ) -> Result<Self::TResult> {
+        Ok( () )
+    }
+}
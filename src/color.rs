@@ -0,0 +1,138 @@
+// Encodes a linear light value with the sRGB transfer function
+pub( crate ) fn linear_to_srgb( value: f32 ) -> f32 {
+    if value <= 0.0031308 {
+        value * 12.92
+    } else {
+        1.055 * value.powf( 1.0 / 2.4 ) - 0.055
+    }
+}
+
+// Converts a value in the range of 0.0 to 1.0 into an 8-bit value, clamping anything outside
+pub( crate ) fn to_8bit( value: f32 ) -> u8 {
+    ( value.clamp( 0.0, 1.0 ) * 255.0 + 0.5 ) as u8
+}
+
+type Matrix = [[f32; 3]; 3];
+
+// CIE XYZ of the D65 white point used by sRGB
+const D65: [f32; 3] = [ 0.95047, 1.0, 1.08883 ];
+
+// Converts CIE XYZ relative to D65 into linear sRGB
+const XYZ_TO_SRGB: Matrix = [
+    [ 3.2404542, -1.5371385, -0.4985314 ],
+    [ -0.969266, 1.8760108, 0.041556 ],
+    [ 0.0556434, -0.2040259, 1.0572252 ],
+];
+
+// Cone response matrices of the Bradford chromatic adaptation
+const BRADFORD: Matrix = [
+    [ 0.8951, 0.2664, -0.1614 ],
+    [ -0.7502, 1.7135, 0.0367 ],
+    [ 0.0389, -0.0685, 1.0296 ],
+];
+
+const BRADFORD_INVERSE: Matrix = [
+    [ 0.9869929, -0.1470543, 0.1599627 ],
+    [ 0.4323053, 0.5183603, 0.0492912 ],
+    [ -0.0085287, 0.0400428, 0.9684867 ],
+];
+
+fn multiply( a: &Matrix, b: &Matrix ) -> Matrix {
+    let mut result = [[0.0; 3]; 3];
+    for row in 0..3 {
+        for column in 0..3 {
+            result[ row ][ column ] = ( 0..3 ).map( |i| a[ row ][ i ] * b[ i ][ column ] ).sum();
+        }
+    }
+
+    result
+}
+
+fn transform( m: &Matrix, v: [f32; 3] ) -> [f32; 3] {
+    [
+        m[ 0 ][ 0 ] * v[ 0 ] + m[ 0 ][ 1 ] * v[ 1 ] + m[ 0 ][ 2 ] * v[ 2 ],
+        m[ 1 ][ 0 ] * v[ 0 ] + m[ 1 ][ 1 ] * v[ 1 ] + m[ 1 ][ 2 ] * v[ 2 ],
+        m[ 2 ][ 0 ] * v[ 0 ] + m[ 2 ][ 1 ] * v[ 1 ] + m[ 2 ][ 2 ] * v[ 2 ],
+    ]
+}
+
+// Adapts CIE XYZ colors from the given white point to D65
+fn adapt_to_d65( white: [f32; 3] ) -> Matrix {
+    let source = transform( &BRADFORD, white );
+    let target = transform( &BRADFORD, D65 );
+    let scale = [
+        [ target[ 0 ] / source[ 0 ], 0.0, 0.0 ],
+        [ 0.0, target[ 1 ] / source[ 1 ], 0.0 ],
+        [ 0.0, 0.0, target[ 2 ] / source[ 2 ] ],
+    ];
+
+    multiply( &BRADFORD_INVERSE, &multiply( &scale, &BRADFORD ) )
+}
+
+// Converts the colors of an RGB color space given by its primaries and tone curves into sRGB
+pub( crate ) struct Transform {
+    matrix: Matrix,
+    curves: [[f32; 256]; 3],
+}
+
+impl Transform {
+    // Primaries are the CIE XYZ coordinates of red, green and blue, their sum is the white point.
+    // Curves map each 8-bit channel value to linear light.
+    pub( crate ) fn new( red: [f32; 3], green: [f32; 3], blue: [f32; 3], curves: [[f32; 256]; 3] )
+        -> Option<Transform> {
+
+        let white = [
+            red[ 0 ] + green[ 0 ] + blue[ 0 ],
+            red[ 1 ] + green[ 1 ] + blue[ 1 ],
+            red[ 2 ] + green[ 2 ] + blue[ 2 ],
+        ];
+
+        if white.iter().any( |&w| w.is_nan() || w <= 0.0 ) {
+            return None;
+        }
+
+        // Normalize to a white luminance of one
+        let primaries = [
+            [ red[ 0 ] / white[ 1 ], green[ 0 ] / white[ 1 ], blue[ 0 ] / white[ 1 ] ],
+            [ red[ 1 ] / white[ 1 ], green[ 1 ] / white[ 1 ], blue[ 1 ] / white[ 1 ] ],
+            [ red[ 2 ] / white[ 1 ], green[ 2 ] / white[ 1 ], blue[ 2 ] / white[ 1 ] ],
+        ];
+        let white = [ white[ 0 ] / white[ 1 ], 1.0, white[ 2 ] / white[ 1 ] ];
+
+        let matrix = multiply( &XYZ_TO_SRGB, &multiply( &adapt_to_d65( white ), &primaries ) );
+
+        Some( Transform {
+            matrix,
+            curves,
+        } )
+    }
+
+    // Uses a simple power function as tone curve of each channel
+    pub( crate ) fn with_gamma( red: [f32; 3], green: [f32; 3], blue: [f32; 3], gamma: [f32; 3] )
+        -> Option<Transform> {
+
+        let mut curves = [[0.0; 256]; 3];
+        for ( curve, &gamma ) in curves.iter_mut().zip( gamma.iter() ) {
+            for ( value, entry ) in curve.iter_mut().enumerate() {
+                *entry = ( value as f32 / 255.0 ).powf( gamma );
+            }
+        }
+
+        Transform::new( red, green, blue, curves )
+    }
+
+    pub( crate ) fn apply( &self, r: u8, g: u8, b: u8 ) -> ( u8, u8, u8 ) {
+        let ( r, g, b ) = self.apply_linear(
+            self.curves[ 0 ][ r as usize ],
+            self.curves[ 1 ][ g as usize ],
+            self.curves[ 2 ][ b as usize ] );
+
+        ( to_8bit( linear_to_srgb( r ) ), to_8bit( linear_to_srgb( g ) ), to_8bit( linear_to_srgb( b ) ) )
+    }
+
+    // Converts linear light values, which need no tone curves, into linear sRGB
+    pub( crate ) fn apply_linear( &self, r: f32, g: f32, b: f32 ) -> ( f32, f32, f32 ) {
+        let linear = transform( &self.matrix, [ r, g, b ] );
+        ( linear[ 0 ], linear[ 1 ], linear[ 2 ] )
+    }
+}
@@ -0,0 +1,315 @@
+use std::io::{
+    Result,
+    Read,
+    Seek,
+    SeekFrom,
+};
+
+use bitmap;
+use bitmap::{
+    new_data_error,
+    new_unsupported_error,
+    ColorSpace,
+    FileType,
+    FileHeader,
+    Intent,
+    Header,
+    FILE_HEADER_SIZE,
+};
+use color::Transform;
+use embedded::Embedded;
+use icc;
+use icc::IccProfile;
+use info::BitmapInfo;
+use profile;
+use profile::Profile;
+
+/// Options that change how the pixels of a bitmap are passed to the builder.
+#[derive( Debug, PartialEq, Eq, Clone, Copy, Default )]
+pub struct Options {
+    /// Passes all pixels as fully opaque, ignoring alpha channels and masks
+    pub ignore_alpha: bool,
+    /// Converts the colors of bitmaps with a calibrated RGB color space or an ICC profile to
+    /// sRGB. Embedded profiles usually follow the pixels, so they are only used if the decoder
+    /// was created by `Decoder::from_seekable` or the profile was set by `set_icc_profile`.
+    pub to_srgb: bool,
+    /// Rejects bitmaps with unknown color space types or rendering intents instead of
+    /// ignoring them
+    pub strict: bool,
+}
+
+/// Decodes a bitmap file in two steps.
+///
+/// The headers are read when the decoder is created and can be inspected before the pixels are
+/// decoded, which consumes the decoder. Only bitmap files are supported, device dependent and RIFF
+/// bitmaps have to be decoded with `bmp_rs::decode`.
+pub struct Decoder<TInput: Read> {
+    input: TInput,
+    header: Header,
+    info: BitmapInfo,
+    options: Options,
+    icc: Option<IccProfile>,
+    position: u64, // Number of bytes read so far
+}
+
+impl<TInput: Read> Decoder<TInput> {
+    /// Reads the headers of a bitmap file using the default options.
+    pub fn new( input: TInput ) -> Result<Decoder<TInput>> {
+        Decoder::with_options( input, Options::default() )
+    }
+
+    /// Reads the headers of a bitmap file using the given options.
+    pub fn with_options( mut input: TInput, options: Options ) -> Result<Decoder<TInput>> {
+        // Read file header
+        let file_type = FileType::from_reader( &mut input )?;
+        match file_type {
+            FileType::DeviceIndependentBitmap => {},
+            _ => return Err( new_unsupported_error( "Decoder", file_type ) ),
+        }
+        let file = FileHeader::from_reader( &mut input, file_type )?;
+
+        // Read info header
+        let header = bitmap::read_header( &mut input )?;
+        let info = BitmapInfo::new( file.file_type, file.file_size, file.data_offset, &header );
+        let position = FILE_HEADER_SIZE + header.size() as u64;
+
+        if options.strict {
+            validate( &header )?;
+        }
+
+        Ok( Decoder {
+            input,
+            header,
+            info,
+            options,
+            icc: None,
+            position,
+        } )
+    }
+
+    /// Returns the headers read on creation.
+    pub fn info( &self ) -> &BitmapInfo {
+        &self.info
+    }
+
+    /// Returns the options used for decoding.
+    pub fn options( &self ) -> &Options {
+        &self.options
+    }
+
+    /// Changes the options used for decoding.
+    pub fn set_options( &mut self, options: Options ) {
+        self.options = options;
+    }
+
+    /// Sets the ICC profile whose colors are converted to sRGB if `Options::to_srgb` is set. As
+    /// embedded profiles usually follow the pixels, they have to be read by `read_profile` first
+    /// unless the decoder was created by `from_seekable`.
+    pub fn set_icc_profile( &mut self, profile: IccProfile ) {
+        self.icc = Some( profile );
+    }
+
+    /// Decodes the pixels of the bitmap using the given builder.
+    pub fn decode<TBuilder: super::Builder>( mut self, mut builder: TBuilder ) -> Result<TBuilder> {
+        if self.options.strict {
+            validate( &self.header )?;
+        }
+
+        let offset = self.info.data_offset as u64;
+        bitmap::skip_to( &mut self.input, self.position, offset )?;
+
+        let transform = match ( self.options.to_srgb, &self.icc ) {
+            ( true, Some( icc ) ) => icc.to_transform(),
+            ( true, None ) => calibrated_transform( &self.header ),
+            ( false, _ ) => None,
+        };
+
+        let mut output = OptionsBuilder {
+            builder: &mut builder,
+            options: self.options,
+            transform,
+        };
+        bitmap::decode_pixels( &mut self.input, &self.header, offset, &mut output )?;
+
+        Ok( builder )
+    }
+}
+
+impl<TInput: Read + Seek> Decoder<TInput> {
+    /// Reads the headers of a bitmap file using the given options. If `Options::to_srgb` is set,
+    /// an embedded ICC profile is read as well and used for the conversion. Profiles that can't be
+    /// parsed are ignored unless decoding is strict.
+    pub fn from_seekable( input: TInput, options: Options ) -> Result<Decoder<TInput>> {
+        let mut decoder = Decoder::with_options( input, options )?;
+
+        if options.to_srgb {
+            if let Some( Profile::Embedded( data ) ) = decoder.read_profile()? {
+                match icc::read_icc( &data ) {
+                    Ok( icc ) => decoder.icc = Some( icc ),
+                    Err( e ) if options.strict => return Err( e ),
+                    Err( _ ) => {},
+                }
+            }
+        }
+
+        Ok( decoder )
+    }
+
+    /// Reads the embedded or linked color profile of the bitmap, if there is one. The input is
+    /// left at its current position, so pixels can still be decoded afterwards.
+    pub fn read_profile( &mut self ) -> Result<Option<Profile>> {
+        let position = self.input.stream_position()?;
+        let base = position.checked_sub( self.position )
+            .ok_or( new_data_error( "Invalid decoder position" ) )?;
+
+        let profile = profile::read_profile( &mut self.input, base + FILE_HEADER_SIZE, &self.header );
+        self.input.seek( SeekFrom::Start( position ) )?;
+
+        profile
+    }
+}
+
+// Checks the header values that are ignored unless decoding is strict
+fn validate( header: &Header ) -> Result<()> {
+    if let Some( ref e ) = header.extra {
+        if ColorSpace::from_u32( e.color_space_type ).is_none() {
+            return Err( new_data_error(
+                format!( "Invalid color space type 0x{:X}", e.color_space_type ) ) );
+        }
+    }
+
+    if let Some( ref p ) = header.profile {
+        if Intent::from_u32( p.intent ).is_none() {
+            return Err( new_data_error( format!( "Invalid rendering intent {}", p.intent ) ) );
+        }
+    }
+
+    Ok( () )
+}
+
+// Builds the conversion to sRGB from the endpoints and gamma values of a calibrated color space.
+// A gamma of zero is taken as a linear tone curve.
+fn calibrated_transform( header: &Header ) -> Option<Transform> {
+    let e = match header.extra {
+        Some( ref e ) if ColorSpace::from_u32( e.color_space_type ) == Some( ColorSpace::CalibratedRgb ) => e,
+        _ => return None,
+    };
+
+    let endpoint = |x: i32, y: i32, z: i32| [
+        x as f32 / ( 1 << 30 ) as f32,
+        y as f32 / ( 1 << 30 ) as f32,
+        z as f32 / ( 1 << 30 ) as f32,
+    ];
+    let gamma = |g: u32| match g {
+        0 => 1.0,
+        _ => g as f32 / 65536.0,
+    };
+
+    Transform::with_gamma(
+        endpoint( e.red_x, e.red_y, e.red_z ),
+        endpoint( e.green_x, e.green_y, e.green_z ),
+        endpoint( e.blue_x, e.blue_y, e.blue_z ),
+        [ gamma( e.gamma_red ), gamma( e.gamma_green ), gamma( e.gamma_blue ) ] )
+}
+
+// Applies the decoder options to the pixels before passing them to the actual builder
+struct OptionsBuilder<'a, TBuilder: 'a + super::Builder> {
+    builder: &'a mut TBuilder,
+    options: Options,
+    transform: Option<Transform>,
+}
+
+impl<'a, TBuilder: super::Builder> super::Builder for OptionsBuilder<'a, TBuilder> {
+    type TResult = TBuilder::TResult;
+
+    fn set_size( &mut self, width: u32, height: u32 ) {
+        self.builder.set_size( width, height );
+    }
+
+    fn set_pixel( &mut self, x: u32, y: u32, r: u8, g: u8, b: u8, a: u8 ) {
+        let a = match self.options.ignore_alpha {
+            true => 255,
+            false => a,
+        };
+
+        let ( r, g, b ) = match self.transform {
+            Some( ref t ) => t.apply( r, g, b ),
+            None => ( r, g, b ),
+        };
+
+        self.builder.set_pixel( x, y, r, g, b, a );
+    }
+
+    fn set_pixel_linear( &mut self, x: u32, y: u32, r: f32, g: f32, b: f32, a: f32 ) {
+        let a = match self.options.ignore_alpha {
+            true => 1.0,
+            false => a,
+        };
+
+        let ( r, g, b ) = match self.transform {
+            Some( ref t ) => t.apply_linear( r, g, b ),
+            None => ( r, g, b ),
+        };
+
+        self.builder.set_pixel_linear( x, y, r, g, b, a );
+    }
+
+    fn set_embedded( &mut self, embedded: &Embedded ) -> Result<()> {
+        self.builder.set_embedded( embedded )
+    }
+
+    fn build( &mut self ) -> Result<Self::TResult> {
+        self.builder.build()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use bitmap::tests::bitmap;
+    use canvas::Canvas;
+    use icc::{
+        IccProfile,
+        ToneCurve,
+    };
+    use super::{
+        Decoder,
+        Options,
+    };
+
+    // A linear profile with the sRGB primaries, except that red and green are swapped
+    fn swapped() -> IccProfile {
+        IccProfile {
+            red: [ 0.3851, 0.7169, 0.0971 ],
+            green: [ 0.4361, 0.2225, 0.0139 ],
+            blue: [ 0.1431, 0.0606, 0.7141 ],
+            white: [ 0.9642, 1.0, 0.8249 ],
+            red_curve: ToneCurve::Gamma( 1.0 ),
+            green_curve: ToneCurve::Gamma( 1.0 ),
+            blue_curve: ToneCurve::Gamma( 1.0 ),
+        }
+    }
+
+    fn decode_srgb( file: &[u8] ) -> ( u8, u8, u8, u8 ) {
+        let options = Options { to_srgb: true, ..Options::default() };
+        let mut decoder = Decoder::with_options( Cursor::new( file ), options ).unwrap();
+        decoder.set_icc_profile( swapped() );
+
+        decoder.decode( Canvas::new() ).unwrap().pixel( 0, 0 )
+    }
+
+    #[test]
+    fn converts_pixels_to_srgb() {
+        // Half of the red channel, encoded linearly
+        let file = bitmap( 40, 1, 1, 24, 0, &[], &[ 0, 0, 128, 0 ] );
+        assert_eq!( decode_srgb( &file ), ( 0, 188, 0, 255 ) );
+    }
+
+    #[test]
+    fn converts_linear_pixels_to_srgb() {
+        // Half of the red channel in s2.13 fixed-point and an opaque alpha
+        let file = bitmap( 40, 1, 1, 64, 0, &[], &[ 0, 0, 0, 0, 0, 0x10, 0, 0x20 ] );
+        assert_eq!( decode_srgb( &file ), ( 0, 188, 0, 255 ) );
+    }
+}
@@ -0,0 +1,81 @@
+use std::io::{
+    Result,
+    Read,
+};
+
+use bitmap;
+use bitmap::{
+    FileType,
+    new_data_error,
+    FILE_HEADER_SIZE,
+};
+
+/// Format of an image stream embedded into a bitmap.
+#[derive( Debug, PartialEq, Eq, Clone, Copy )]
+pub enum EmbeddedKind {
+    Jpeg,
+    Png,
+}
+
+/// A complete JPEG or PNG stream that makes up the pixel data of a bitmap.
+#[derive( Debug, PartialEq, Eq, Clone )]
+pub struct Embedded {
+    pub kind: EmbeddedKind,
+    /// Offset of the stream within the file
+    pub offset: u64,
+    pub length: u64,
+    pub data: Vec<u8>,
+}
+
+pub( crate ) fn read( input: &mut Read ) -> Result<Embedded> {
+    let file = bitmap::read_file_header( input )?;
+    if file.file_type != FileType::DeviceIndependentBitmap {
+        return Err( new_data_error( "Only device independent bitmaps can embed images" ) );
+    }
+
+    let header = bitmap::read_header( input )?;
+    let position = FILE_HEADER_SIZE + header.size() as u64;
+    let offset = file.data_offset as u64;
+    if offset < position {
+        return Err( new_data_error( format!( "Invalid data offset {} within the headers", offset ) ) );
+    }
+
+    bitmap::skip_to( input, position, offset )?;
+    bitmap::read_embedded( input, &header, offset )?
+        .ok_or( new_data_error( "Bitmap has no embedded JPEG or PNG stream" ) )
+}
+
+#[cfg(test)]
+mod tests {
+    use bitmap::tests::bitmap;
+    use super::{
+        read,
+        EmbeddedKind,
+    };
+
+    // A PNG bitmap whose stream follows a gap of filler bytes
+    fn png_with_gap() -> Vec<u8> {
+        let mut file = bitmap( 0x28, 1, 1, 0, 5, &[], &[ 0xEE, 0xEE, 0x89, b'P', b'N', b'G' ] );
+        file[ 10 ] += 2; // Data offset
+        file[ 34 ] -= 2; // Image size
+
+        file
+    }
+
+    #[test]
+    fn skips_gap_before_stream() {
+        let embedded = read( &mut &png_with_gap()[ .. ] ).unwrap();
+
+        assert_eq!( embedded.kind, EmbeddedKind::Png );
+        assert_eq!( embedded.offset, 56 );
+        assert_eq!( embedded.data, [ 0x89, b'P', b'N', b'G' ] );
+    }
+
+    #[test]
+    fn data_offset_within_headers_is_an_error() {
+        let mut file = png_with_gap();
+        file[ 10 ] = 50;
+
+        assert!( read( &mut &file[ .. ] ).is_err() );
+    }
+}
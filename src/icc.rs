@@ -0,0 +1,296 @@
+use std::io::Result;
+
+use byteorder::{
+    ReadBytesExt,
+    BigEndian,
+};
+
+use bitmap::new_data_error;
+use color::Transform;
+
+const SIGNATURE: u32 = 0x61637370; // 'acsp'
+const RGB: u32 = 0x52474220; // 'RGB '
+const XYZ: u32 = 0x58595A20; // 'XYZ '
+const CURVE: u32 = 0x63757276; // 'curv'
+const PARAMETRIC: u32 = 0x70617261; // 'para'
+
+/// The tone curve of a single channel of an ICC profile, mapping encoded values to linear light.
+#[derive( Debug, PartialEq, Clone )]
+pub enum ToneCurve {
+    /// A simple power function
+    Gamma( f32 ),
+    /// Evenly spaced samples that are interpolated linearly
+    Table( Vec<u16> ),
+    /// The parameters g, a, b, c, d, e and f of the ICC type 4 parametric function, which
+    /// all other parametric function types are converted to
+    Parametric( [f32; 7] ),
+}
+
+/// An ICC profile of the matrix/TRC kind, which describes an RGB color space by the CIE XYZ
+/// coordinates of its primaries relative to D50 and a tone curve per channel.
+#[derive( Debug, PartialEq, Clone )]
+pub struct IccProfile {
+    pub red: [f32; 3],
+    pub green: [f32; 3],
+    pub blue: [f32; 3],
+    pub white: [f32; 3],
+    pub red_curve: ToneCurve,
+    pub green_curve: ToneCurve,
+    pub blue_curve: ToneCurve,
+}
+
+impl ToneCurve {
+    /// Maps an encoded value in the range of 0.0 to 1.0 to linear light.
+    pub fn evaluate( &self, value: f32 ) -> f32 {
+        let x = value.clamp( 0.0, 1.0 );
+
+        match *self {
+            ToneCurve::Gamma( gamma ) => x.powf( gamma ),
+            ToneCurve::Table( ref table ) => match table.len() {
+                0 => x,
+                1 => table[ 0 ] as f32 / 65535.0,
+                n => {
+                    let position = x * ( n - 1 ) as f32;
+                    let index = ( position as usize ).min( n - 2 );
+                    let fraction = position - index as f32;
+
+                    ( table[ index ] as f32 * ( 1.0 - fraction )
+                        + table[ index + 1 ] as f32 * fraction ) / 65535.0
+                },
+            },
+            ToneCurve::Parametric( [ g, a, b, c, d, e, f ] ) => match x >= d {
+                true => ( a * x + b ).max( 0.0 ).powf( g ) + e,
+                false => c * x + f,
+            },
+        }
+    }
+}
+
+impl IccProfile {
+    // Builds the conversion to sRGB, which treats the profile's colors as media-relative
+    pub( crate ) fn to_transform( &self ) -> Option<Transform> {
+        let mut curves = [[0.0; 256]; 3];
+        let channels = [ &self.red_curve, &self.green_curve, &self.blue_curve ];
+
+        for ( curve, channel ) in curves.iter_mut().zip( channels.iter() ) {
+            for ( value, entry ) in curve.iter_mut().enumerate() {
+                *entry = channel.evaluate( value as f32 / 255.0 );
+            }
+        }
+
+        Transform::new( self.red, self.green, self.blue, curves )
+    }
+}
+
+fn read_u32( data: &[u8], offset: usize ) -> Result<u32> {
+    ( &data[ offset.min( data.len() ).. ] ).read_u32::<BigEndian>()
+}
+
+fn read_u16( data: &[u8], offset: usize ) -> Result<u16> {
+    ( &data[ offset.min( data.len() ).. ] ).read_u16::<BigEndian>()
+}
+
+// Reads a s15Fixed16Number
+fn read_fixed( data: &[u8], offset: usize ) -> Result<f32> {
+    Ok( ( &data[ offset.min( data.len() ).. ] ).read_i32::<BigEndian>()? as f32 / 65536.0 )
+}
+
+// Returns the data of a tag by looking it up in the tag table
+fn find_tag<'a>( data: &'a [u8], signature: &[u8; 4] ) -> Result<&'a [u8]> {
+    let count = read_u32( data, 128 )? as usize;
+
+    for entry in 0..count {
+        let position = 132 + entry * 12;
+        if position + 12 > data.len() {
+            break;
+        }
+
+        if data.get( position..position + 4 ) != Some( &signature[ .. ] ) {
+            continue;
+        }
+
+        let offset = read_u32( data, position + 4 )? as usize;
+        let size = read_u32( data, position + 8 )? as usize;
+
+        return data.get( offset..offset.saturating_add( size ) )
+            .ok_or( new_data_error( "Invalid ICC tag offset" ) );
+    }
+
+    Err( new_data_error( format!(
+        "ICC profile has no {} tag", String::from_utf8_lossy( signature ) ) ) )
+}
+
+fn read_xyz( data: &[u8], signature: &[u8; 4] ) -> Result<[f32; 3]> {
+    let tag = find_tag( data, signature )?;
+    if read_u32( tag, 0 )? != XYZ {
+        return Err( new_data_error( "Invalid ICC XYZ tag" ) );
+    }
+
+    Ok( [ read_fixed( tag, 8 )?, read_fixed( tag, 12 )?, read_fixed( tag, 16 )? ] )
+}
+
+fn read_curve( data: &[u8], signature: &[u8; 4] ) -> Result<ToneCurve> {
+    let tag = find_tag( data, signature )?;
+
+    match read_u32( tag, 0 )? {
+        CURVE => {
+            let count = read_u32( tag, 8 )? as usize;
+            match count {
+                0 => Ok( ToneCurve::Gamma( 1.0 ) ),
+                1 => Ok( ToneCurve::Gamma( read_u16( tag, 12 )? as f32 / 256.0 ) ), // u8Fixed8Number
+                _ => {
+                    let mut table = Vec::with_capacity( count.min( tag.len() / 2 ) );
+                    for index in 0..count {
+                        table.push( read_u16( tag, 12 + index * 2 )? );
+                    }
+
+                    Ok( ToneCurve::Table( table ) )
+                },
+            }
+        },
+        PARAMETRIC => {
+            let kind = read_u16( tag, 8 )?;
+            let count = match kind {
+                0 => 1,
+                1 => 3,
+                2 => 4,
+                3 => 5,
+                4 => 7,
+                x => return Err( new_data_error(
+                    format!( "Invalid ICC parametric curve type {}", x ) ) ),
+            };
+
+            let mut p = [0.0; 7];
+            for ( index, parameter ) in p.iter_mut().take( count ).enumerate() {
+                *parameter = read_fixed( tag, 12 + index * 4 )?;
+            }
+
+            // Convert to g, a, b, c, d, e, f. Types 1 and 2 start their curve at -b / a.
+            let [ g, a, b, c, d, e, f ] = p;
+            if ( kind == 1 || kind == 2 ) && a == 0.0 {
+                return Err( new_data_error( "Invalid ICC parametric curve with a of zero" ) );
+            }
+
+            Ok( ToneCurve::Parametric( match kind {
+                0 => [ g, 1.0, 0.0, 0.0, 0.0, 0.0, 0.0 ],
+                1 => [ g, a, b, 0.0, -b / a, 0.0, 0.0 ],
+                2 => [ g, a, b, 0.0, -b / a, c, c ],
+                3 => [ g, a, b, c, d, 0.0, 0.0 ],
+                _ => [ g, a, b, c, d, e, f ],
+            } ) )
+        },
+        _ => Err( new_data_error( "Invalid ICC curve tag" ) ),
+    }
+}
+
+pub( crate ) fn read_icc( data: &[u8] ) -> Result<IccProfile> {
+    // Read profile header
+    if read_u32( data, 36 )? != SIGNATURE {
+        return Err( new_data_error( "Invalid ICC profile signature" ) );
+    }
+
+    if read_u32( data, 16 )? != RGB || read_u32( data, 20 )? != XYZ {
+        return Err( new_data_error( "Only RGB ICC profiles with XYZ connection space are supported" ) );
+    }
+
+    Ok( IccProfile {
+        red: read_xyz( data, b"rXYZ" )?,
+        green: read_xyz( data, b"gXYZ" )?,
+        blue: read_xyz( data, b"bXYZ" )?,
+        white: read_xyz( data, b"wtpt" )?,
+        red_curve: read_curve( data, b"rTRC" )?,
+        green_curve: read_curve( data, b"gTRC" )?,
+        blue_curve: read_curve( data, b"bTRC" )?,
+    } )
+}
+
+#[cfg(test)]
+mod tests {
+    use byteorder::{
+        WriteBytesExt,
+        BigEndian,
+    };
+
+    use super::{
+        read_icc,
+        find_tag,
+        ToneCurve,
+    };
+
+    // A matrix/TRC profile with the sRGB primaries whose red tone curve tag holds the given data
+    // and whose other curves are linear
+    fn profile( tag_count: u32, red_curve: &[u8] ) -> Vec<u8> {
+        let mut data = vec![ 0; 128 ];
+        data[ 16..20 ].copy_from_slice( b"RGB " );
+        data[ 20..24 ].copy_from_slice( b"XYZ " );
+        data[ 36..40 ].copy_from_slice( b"acsp" );
+
+        let mut tags = Vec::new();
+        for &( signature, xyz ) in [
+            ( b"rXYZ", [ 0.4361, 0.2225, 0.0139 ] ),
+            ( b"gXYZ", [ 0.3851, 0.7169, 0.0971 ] ),
+            ( b"bXYZ", [ 0.1431, 0.0606, 0.7141 ] ),
+            ( b"wtpt", [ 0.9642, 1.0, 0.8249 ] ),
+        ].iter() {
+            let mut tag = b"XYZ \0\0\0\0".to_vec();
+            for &value in xyz.iter() {
+                tag.write_i32::<BigEndian>( ( value * 65536.0f32 ).round() as i32 ).unwrap();
+            }
+            tags.push( ( signature, tag ) );
+        }
+        tags.push( ( b"rTRC", red_curve.to_vec() ) );
+        tags.push( ( b"gTRC", b"curv\0\0\0\0\0\0\0\0".to_vec() ) );
+        tags.push( ( b"bTRC", b"curv\0\0\0\0\0\0\0\0".to_vec() ) );
+
+        data.write_u32::<BigEndian>( tag_count ).unwrap();
+        let mut offset = 132 + tags.len() * 12;
+        for ( signature, tag ) in tags.iter() {
+            data.extend_from_slice( &signature[ .. ] );
+            data.write_u32::<BigEndian>( offset as u32 ).unwrap();
+            data.write_u32::<BigEndian>( tag.len() as u32 ).unwrap();
+            offset += tag.len();
+        }
+        for ( _, tag ) in tags.iter() {
+            data.extend_from_slice( tag );
+        }
+
+        data
+    }
+
+    // A parametric curve of type 1 with the given gamma, a and b
+    fn parametric( g: f32, a: f32, b: f32 ) -> Vec<u8> {
+        let mut tag = b"para\0\0\0\0\0\x01\0\0".to_vec();
+        for &value in [ g, a, b ].iter() {
+            tag.write_i32::<BigEndian>( ( value * 65536.0 ).round() as i32 ).unwrap();
+        }
+
+        tag
+    }
+
+    #[test]
+    fn reads_matrix_trc_profile() {
+        let data = profile( 7, b"curv\0\0\0\0\0\0\0\x03\0\0\x80\0\xFF\xFF" );
+        let icc = read_icc( &data ).unwrap();
+
+        assert!( ( icc.green[ 1 ] - 0.7169 ).abs() < 0.0001 );
+        assert!( ( icc.white[ 2 ] - 0.8249 ).abs() < 0.0001 );
+        assert_eq!( icc.red_curve, ToneCurve::Table( vec![ 0, 0x8000, 0xFFFF ] ) );
+        assert_eq!( icc.green_curve, ToneCurve::Gamma( 1.0 ) );
+
+        let data = profile( 7, &parametric( 2.0, 0.5, -0.25 ) );
+        assert_eq!( read_icc( &data ).unwrap().red_curve,
+            ToneCurve::Parametric( [ 2.0, 0.5, -0.25, 0.0, 0.5, 0.0, 0.0 ] ) );
+    }
+
+    #[test]
+    fn tag_count_beyond_data_is_an_error() {
+        let data = profile( 0xFFFFFFFF, b"curv\0\0\0\0\0\0\0\0" );
+        assert!( find_tag( &data, b"desc" ).is_err() );
+    }
+
+    #[test]
+    fn parametric_curve_with_a_of_zero_is_an_error() {
+        let data = profile( 7, &parametric( 2.0, 0.0, 0.5 ) );
+        assert!( read_icc( &data ).is_err() );
+    }
+}
@@ -0,0 +1,350 @@
+use std::io::{
+    Result,
+    Read,
+    Seek,
+    SeekFrom,
+};
+
+use byteorder::{
+    ReadBytesExt,
+    BigEndian,
+    LittleEndian,
+};
+
+use bitmap;
+use bitmap::new_data_error;
+use canvas::Canvas;
+use embedded::{
+    Embedded,
+    EmbeddedKind,
+};
+
+const PNG_SIGNATURE: [u8; 8] = [ 0x89, 0x50, 0x4E, 0x47, 0x0D, 0x0A, 0x1A, 0x0A ];
+
+/// Denotes whether an icon file holds icons or cursors.
+#[derive( Debug, PartialEq, Eq, Clone, Copy )]
+pub enum IconKind {
+    Icon,
+    Cursor,
+}
+
+/// A single image within a Windows icon or cursor file.
+///
+/// Only cursors have a hotspot. PNG compressed images are passed to `Builder::set_embedded`
+/// when decoded.
+#[derive( Debug, PartialEq, Eq, Clone, Copy )]
+pub struct IconEntry {
+    pub kind: IconKind,
+    pub width: u32,
+    pub height: u32,
+    pub bpp: u32,
+    pub hotspot: Option<( u16, u16 )>,
+    pub png: bool,
+    /// Size of the image data in bytes
+    pub size: u32,
+    /// Offset of the image data within the input
+    pub offset: u64,
+}
+
+// Returns width, height and bits per pixel of a PNG image by reading its IHDR chunk
+fn read_png_header( input: &mut Read ) -> Result<( u32, u32, u32 )> {
+    let mut signature = [0; 8];
+    input.read_exact( &mut signature )?;
+    input.read_u32::<BigEndian>()?; // Chunk length
+
+    let mut chunk = [0; 4];
+    input.read_exact( &mut chunk )?;
+    if signature != PNG_SIGNATURE || &chunk != b"IHDR" {
+        return Err( new_data_error( "Invalid PNG header" ) );
+    }
+
+    let width = input.read_u32::<BigEndian>()?;
+    let height = input.read_u32::<BigEndian>()?;
+    let depth = input.read_u8()? as u32;
+    let channels = match input.read_u8()? {
+        2 => 3, // RGB
+        4 => 2, // Grayscale with alpha
+        6 => 4, // RGB with alpha
+        _ => 1, // Grayscale or palette
+    };
+
+    Ok( ( width, height, depth * channels ) )
+}
+
+fn is_png<TInput: Read + Seek>( input: &mut TInput, offset: u64 ) -> Result<bool> {
+    let mut signature = [0; 8];
+    input.seek( SeekFrom::Start( offset ) )?;
+    input.read_exact( &mut signature )?;
+
+    Ok( signature == PNG_SIGNATURE )
+}
+
+pub( crate ) fn read_icon_dir<TInput: Read + Seek>( input: &mut TInput ) -> Result<Vec<IconEntry>> {
+    // All offsets are relative to the start of the file
+    let base = input.stream_position()?;
+
+    // Read icon header
+    let reserved = input.read_u16::<LittleEndian>()?;
+    let kind = match input.read_u16::<LittleEndian>()? {
+        1 if reserved == 0 => IconKind::Icon,
+        2 if reserved == 0 => IconKind::Cursor,
+        x => return Err( new_data_error( format!( "Invalid icon type {}", x ) ) ),
+    };
+    let count = input.read_u16::<LittleEndian>()?;
+
+    // Read directory entries
+    let mut entries = Vec::with_capacity( count as usize );
+    for _ in 0..count {
+        let width = match input.read_u8()? {
+            0 => 256,
+            x => x as u32,
+        };
+        let height = match input.read_u8()? {
+            0 => 256,
+            x => x as u32,
+        };
+        input.read_u8()?; // Color count
+        input.read_u8()?; // Reserved
+
+        // Icons store planes and bits per pixel where cursors store their hotspot
+        let x = input.read_u16::<LittleEndian>()?;
+        let y = input.read_u16::<LittleEndian>()?;
+        let hotspot = match kind {
+            IconKind::Cursor => Some( ( x, y ) ),
+            IconKind::Icon => None,
+        };
+
+        let size = input.read_u32::<LittleEndian>()?;
+        let offset = base + input.read_u32::<LittleEndian>()? as u64;
+
+        entries.push( IconEntry {
+            kind,
+            width,
+            height,
+            bpp: 0,
+            hotspot,
+            png: false,
+            size,
+            offset,
+        } );
+    }
+
+    for entry in entries.iter_mut() {
+        read_image_format( input, entry )?;
+    }
+
+    Ok( entries )
+}
+
+// Directories aren't reliable about bits per pixel, so the image itself is consulted
+pub( crate ) fn read_image_format<TInput: Read + Seek>( input: &mut TInput, entry: &mut IconEntry )
+    -> Result<()> {
+
+    entry.png = is_png( input, entry.offset )?;
+    input.seek( SeekFrom::Start( entry.offset ) )?;
+
+    entry.bpp = match entry.png {
+        true => read_png_header( input )?.2,
+        false => bitmap::read_header( input )?.core.bpp,
+    };
+
+    Ok( () )
+}
+
+// Decodes an icon or cursor image, which is either a PNG stream or a packed bitmap of twice
+// the height holding the colors followed by a 1-bit AND mask.
+pub( crate ) fn decode_image<TInput: Read + Seek, TBuilder: super::Builder>(
+    input: &mut TInput, offset: u64, size: u32, hotspot: Option<( u16, u16 )>, mut builder: TBuilder )
+    -> Result<TBuilder> {
+
+    if is_png( input, offset )? {
+        input.seek( SeekFrom::Start( offset ) )?;
+        let ( width, height, _ ) = read_png_header( input )?;
+
+        let mut data = Vec::new();
+        input.seek( SeekFrom::Start( offset ) )?;
+        input.take( size as u64 ).read_to_end( &mut data )?;
+        if data.len() != size as usize {
+            return Err( new_data_error( "Unexpected end of PNG icon image" ) );
+        }
+
+        builder.set_size( width, height );
+        if let Some( ( x, y ) ) = hotspot {
+            builder.set_hotspot( x as u32, y as u32 );
+        }
+
+        builder.set_embedded( &Embedded {
+            kind: EmbeddedKind::Png,
+            offset,
+            length: size as u64,
+            data,
+        } )?;
+
+        return Ok( builder );
+    }
+
+    // Read header
+    input.seek( SeekFrom::Start( offset ) )?;
+    let mut header = bitmap::read_header( input )?;
+    header.core.height /= 2;
+    header.set_implicit_alpha();
+
+    let width = header.core.width;
+    let height = header.core.height;
+    Canvas::check_size( width, height )?;
+
+    // Read colors and AND mask
+    let mut colors = Canvas::new();
+    bitmap::decode_pixels( input, &header, offset + header.size() as u64, &mut colors )?;
+
+    let alpha = header.core.bpp == 32
+        && ( 0..height ).any( |y| ( 0..width ).any( |x| colors.pixel( x, y ).3 != 0 ) );
+
+    let mut mask = Canvas::new();
+    match bitmap::decode_mask( input, width, height, header.core.top_down, &mut mask ) {
+        Ok( _ ) => {},
+        Err( _ ) if alpha => {}, // Some writers omit the mask when alpha is present
+        Err( e ) => return Err( e ),
+    }
+
+    builder.set_size( width, height );
+    if let Some( ( x, y ) ) = hotspot {
+        builder.set_hotspot( x as u32, y as u32 );
+    }
+
+    for y in 0..height {
+        for x in 0..width {
+            let ( r, g, b, a ) = colors.pixel( x, y );
+
+            if alpha {
+                builder.set_pixel( x, y, r, g, b, a );
+
+            } else if mask.pixel( x, y ).0 == 0 {
+                builder.set_pixel( x, y, r, g, b, 255 );
+
+            } else if r == 0 && g == 0 && b == 0 {
+                builder.set_pixel( x, y, 0, 0, 0, 0 );
+
+            } else {
+                builder.set_pixel( x, y, 0, 0, 0, 255 );
+                builder.set_inverted( x, y );
+            }
+        }
+    }
+
+    Ok( builder )
+}
+
+pub( crate ) fn decode_entry<TInput: Read + Seek, TBuilder: super::Builder>(
+    input: &mut TInput, entry: &IconEntry, builder: TBuilder ) -> Result<TBuilder> {
+
+    decode_image( input, entry.offset, entry.size, entry.hotspot, builder )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{
+        Cursor,
+        Result,
+    };
+
+    use byteorder::{
+        WriteBytesExt,
+        LittleEndian,
+    };
+
+    use canvas::Canvas;
+    use embedded::Embedded;
+    use super::{
+        read_icon_dir,
+        decode_entry,
+        PNG_SIGNATURE,
+    };
+
+    // Keeps the embedded stream passed to the builder
+    struct EmbeddedBuilder {
+        size: ( u32, u32 ),
+        embedded: Option<Embedded>,
+    }
+
+    impl ::Builder for EmbeddedBuilder {
+        type TResult = ();
+
+        fn set_size( &mut self, width: u32, height: u32 ) {
+            self.size = ( width, height );
+        }
+
+        fn set_pixel( &mut self, _: u32, _: u32, _: u8, _: u8, _: u8, _: u8 ) {
+        }
+
+        fn set_embedded( &mut self, embedded: &Embedded ) -> Result<()> {
+            self.embedded = Some( embedded.clone() );
+            Ok( () )
+        }
+
+        fn build( &mut self ) -> Result<()> {
+            Ok( () )
+        }
+    }
+
+    // An icon file holding a 1x1 bitmap with alpha and a 3x2 PNG image whose size is given
+    fn icon( png_size: u32 ) -> Vec<u8> {
+        let mut image = Vec::new();
+        for &value in [ 40, 1, 2 ].iter() {
+            image.write_u32::<LittleEndian>( value ).unwrap(); // Size, width and doubled height
+        }
+        image.write_u16::<LittleEndian>( 1 ).unwrap();
+        image.write_u16::<LittleEndian>( 32 ).unwrap();
+        image.resize( 40, 0 );
+        image.extend_from_slice( &[ 0x30, 0x20, 0x10, 0x80 ] ); // Colors
+        image.extend_from_slice( &[ 0, 0, 0, 0 ] ); // AND mask
+
+        let mut png = PNG_SIGNATURE.to_vec();
+        png.extend_from_slice( &[ 0, 0, 0, 13 ] );
+        png.extend_from_slice( b"IHDR" );
+        png.extend_from_slice( &[ 0, 0, 0, 3, 0, 0, 0, 2, 8, 6, 0, 0, 0 ] );
+
+        let mut file = vec![ 0, 0, 1, 0, 2, 0 ];
+        for &( width, height, bpp, size, offset ) in [
+            ( 1, 1, 32, image.len() as u32, 38 ),
+            ( 3, 2, 32, png_size, 38 + image.len() as u32 ),
+        ].iter() {
+            file.extend_from_slice( &[ width, height, 0, 0, 1, 0, bpp, 0 ] );
+            file.write_u32::<LittleEndian>( size ).unwrap();
+            file.write_u32::<LittleEndian>( offset ).unwrap();
+        }
+        file.extend_from_slice( &image );
+        file.extend_from_slice( &png );
+
+        file
+    }
+
+    #[test]
+    fn decodes_bitmap_and_png_entries() {
+        let file = icon( 29 );
+        let mut input = Cursor::new( &file[ .. ] );
+        let entries = read_icon_dir( &mut input ).unwrap();
+
+        assert_eq!( entries.len(), 2 );
+        assert!( !entries[ 0 ].png && entries[ 1 ].png );
+        assert_eq!( ( entries[ 0 ].bpp, entries[ 1 ].bpp ), ( 32, 32 ) );
+
+        let canvas = decode_entry( &mut input, &entries[ 0 ], Canvas::new() ).unwrap();
+        assert_eq!( canvas.pixel( 0, 0 ), ( 0x10, 0x20, 0x30, 0x80 ) );
+
+        let builder = EmbeddedBuilder { size: ( 0, 0 ), embedded: None };
+        let builder = decode_entry( &mut input, &entries[ 1 ], builder ).unwrap();
+        assert_eq!( builder.size, ( 3, 2 ) );
+        assert_eq!( builder.embedded.unwrap().data.len(), 29 );
+    }
+
+    #[test]
+    fn png_size_beyond_input_is_an_error() {
+        let file = icon( 0xFFFFFFFF );
+        let mut input = Cursor::new( &file[ .. ] );
+        let entries = read_icon_dir( &mut input ).unwrap();
+
+        let builder = EmbeddedBuilder { size: ( 0, 0 ), embedded: None };
+        assert!( decode_entry( &mut input, &entries[ 1 ], builder ).is_err() );
+    }
+}
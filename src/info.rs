@@ -0,0 +1,185 @@
+use std::io::{
+    Result,
+    Read,
+};
+
+use bitmap;
+use bitmap::{
+    new_unsupported_error,
+    FileType,
+    FileHeader,
+    Version,
+    Compression,
+    ColorSpace,
+    Intent,
+    Header,
+};
+
+/// The bitfield masks that extract the color channels of 16- and 32-bit pixels.
+#[derive( Debug, PartialEq, Eq, Clone, Copy )]
+pub struct BitmapMasks {
+    pub red: u32,
+    pub green: u32,
+    pub blue: u32,
+    pub alpha: u32,
+}
+
+/// The color space of Version 4 and 5 headers, whose kind is unset if its type is unknown.
+///
+/// Endpoints are CIE XYZ coordinates in 2.30 fixed-point format, gamma values are 16.16 fixed-point
+/// numbers. Both are only used by calibrated color spaces.
+#[derive( Debug, PartialEq, Eq, Clone, Copy )]
+pub struct ColorSpaceInfo {
+    pub color_space_type: u32,
+    pub kind: Option<ColorSpace>,
+    pub red: ( i32, i32, i32 ),
+    pub green: ( i32, i32, i32 ),
+    pub blue: ( i32, i32, i32 ),
+    pub gamma: ( u32, u32, u32 ),
+}
+
+/// The rendering intent and color profile location of Version 5 headers. The intent is unset
+/// if its type is unknown.
+#[derive( Debug, PartialEq, Eq, Clone, Copy )]
+pub struct ProfileInfo {
+    pub intent_type: u32,
+    pub intent: Option<Intent>,
+    /// Offset of the profile data from the start of the info header
+    pub offset: u32,
+    /// Size of the profile data in bytes
+    pub size: u32,
+}
+
+/// The additional fields of IBM OS/2 2.x headers.
+///
+/// The halftoning algorithm given by `rendering` is none (0), error diffusion (1), PANDA (2) or
+/// super-circle (3), whose parameters are `size1` and `size2`. The identifier is reserved for use
+/// by applications.
+#[derive( Debug, PartialEq, Eq, Clone, Copy )]
+pub struct Os2Info {
+    /// Units of the resolution, where 0 means pixels per meter
+    pub units: u16,
+    pub rendering: u16,
+    pub size1: u32,
+    pub size2: u32,
+    pub identifier: u32,
+}
+
+/// The header data of a bitmap file, which is read without decoding any pixels.
+///
+/// Palette colors are given as red, green and blue. Masks are present if the header defines
+/// non-zero bitfield masks.
+#[derive( Debug, PartialEq, Eq, Clone )]
+pub struct BitmapInfo {
+    pub file_type: FileType,
+    pub version: Version,
+    pub file_size: u32,
+    pub data_offset: u32,
+    pub width: u32,
+    pub height: u32,
+    pub top_down: bool,
+    pub bpp: u32,
+    pub planes: u16,
+    pub compression: Option<Compression>,
+    pub image_size: u32,
+    pub masks: Option<BitmapMasks>,
+    pub ppm_x: i32,
+    pub ppm_y: i32,
+    pub used_colors: u32,
+    pub important_colors: u32,
+    pub palette: Vec<( u8, u8, u8 )>,
+    pub color_space: Option<ColorSpaceInfo>,
+    pub profile: Option<ProfileInfo>,
+    pub os2: Option<Os2Info>,
+}
+
+impl BitmapInfo {
+    pub( crate ) fn new( file_type: FileType, file_size: u32, data_offset: u32, header: &Header ) -> BitmapInfo {
+        let mask = &header.bitmask;
+        let masks = match mask.red | mask.green | mask.blue | mask.alpha {
+            0 => None,
+            _ => Some( BitmapMasks {
+                red: mask.red,
+                green: mask.green,
+                blue: mask.blue,
+                alpha: mask.alpha,
+            } ),
+        };
+
+        let color_width = match header.version {
+            Version::Microsoft2 => 3,
+            _ => 4,
+        };
+        let palette = header.palette.chunks( color_width )
+            .map( |c| ( c[ 2 ], c[ 1 ], c[ 0 ] ) )
+            .collect();
+
+        let color_space = header.extra.as_ref().map( |e| ColorSpaceInfo {
+            color_space_type: e.color_space_type,
+            kind: ColorSpace::from_u32( e.color_space_type ),
+            red: ( e.red_x, e.red_y, e.red_z ),
+            green: ( e.green_x, e.green_y, e.green_z ),
+            blue: ( e.blue_x, e.blue_y, e.blue_z ),
+            gamma: ( e.gamma_red, e.gamma_green, e.gamma_blue ),
+        } );
+
+        let profile = header.profile.as_ref().map( |p| ProfileInfo {
+            intent_type: p.intent,
+            intent: Intent::from_u32( p.intent ),
+            offset: p.data,
+            size: p.size,
+        } );
+
+        let os2 = header.os2.as_ref().map( |o| Os2Info {
+            units: o.units,
+            rendering: o.rendering,
+            size1: o.size1,
+            size2: o.size2,
+            identifier: o.identifier,
+        } );
+
+        let ( compression, image_size, ppm_x, ppm_y, used_colors, important_colors ) = match header.info {
+            Some( ref i ) => ( i.compression, i.image_size, i.ppm_x, i.ppm_y, i.used_colors, i.important_colors ),
+            None => ( None, 0, 0, 0, 0, 0 ),
+        };
+
+        BitmapInfo {
+            file_type,
+            version: header.version,
+            file_size,
+            data_offset,
+            width: header.core.width,
+            height: header.core.height,
+            top_down: header.core.top_down,
+            bpp: header.core.bpp,
+            planes: header.core.planes,
+            compression,
+            image_size,
+            masks,
+            ppm_x,
+            ppm_y,
+            used_colors,
+            important_colors,
+            palette,
+            color_space,
+            profile,
+            os2,
+        }
+    }
+}
+
+// Reads the file and info header of a bitmap file. Icons and pointers are described by the
+// header of their mask.
+pub( crate ) fn read_info( input: &mut Read ) -> Result<BitmapInfo> {
+    let file_type = FileType::from_reader( input )?;
+    match file_type {
+        FileType::DeviceDependentBitmap | FileType::Riff | FileType::BitmapArray
+            => return Err( new_unsupported_error( "read_info", file_type ) ),
+        _ => {},
+    }
+    let file = FileHeader::from_reader( input, file_type )?;
+
+    let header = bitmap::read_header( input )?;
+
+    Ok( BitmapInfo::new( file.file_type, file.file_size, file.data_offset, &header ) )
+}
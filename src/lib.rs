@@ -47,12 +47,74 @@
 //!
 extern crate byteorder;
 
+use std::io;
 use std::io::{
     Result,
     Read,
+    Seek,
 };
 
 mod bitmap;
+mod huffman;
+mod array;
+mod canvas;
+mod pointer;
+mod embedded;
+mod color;
+mod icon;
+mod riff;
+mod animation;
+mod metafile;
+mod info;
+mod decoder;
+mod profile;
+mod icc;
+#[cfg( feature = "pe" )]
+mod pe;
+
+pub use array::ArrayEntry;
+pub use bitmap::{
+    FileType,
+    Version,
+    Compression,
+    ColorSpace,
+    Intent,
+};
+pub use info::{
+    BitmapInfo,
+    BitmapMasks,
+    ColorSpaceInfo,
+    ProfileInfo,
+    Os2Info,
+};
+pub use profile::Profile;
+pub use icc::{
+    IccProfile,
+    ToneCurve,
+};
+pub use embedded::{
+    Embedded,
+    EmbeddedKind,
+};
+pub use icon::{
+    IconKind,
+    IconEntry,
+};
+pub use animation::{
+    Animation,
+    AnimationStep,
+};
+pub use metafile::MetafileBitmap;
+pub use decoder::{
+    Decoder,
+    Options,
+};
+#[cfg( feature = "pe" )]
+pub use pe::{
+    Resource,
+    ResourceKind,
+    ResourceName,
+};
 
 pub trait Builder {
     type TResult;
@@ -60,6 +122,38 @@ pub trait Builder {
     fn set_size( &mut self, width: u32, height: u32 );
     fn set_pixel( &mut self, x: u32, y: u32, r: u8, g: u8, b: u8, a: u8 );
     fn build( &mut self ) -> Result<Self::TResult>;
+
+    /// Sets the hotspot of a pointer, measured from the top left corner. Does nothing by default.
+    fn set_hotspot( &mut self, _x: u32, _y: u32 ) {
+    }
+
+    /// Marks a pixel that inverts the screen beneath it. Such pixels are passed to `set_pixel`
+    /// as opaque black beforehand. Does nothing by default.
+    fn set_inverted( &mut self, _x: u32, _y: u32 ) {
+    }
+
+    /// Sets a pixel of a high precision image using linear light values, which may fall outside
+    /// of the range 0.0 to 1.0. By default they are clamped, sRGB encoded and passed to `set_pixel`.
+    fn set_pixel_linear( &mut self, x: u32, y: u32, r: f32, g: f32, b: f32, a: f32 ) {
+        self.set_pixel(
+            x,
+            y,
+            color::to_8bit( color::linear_to_srgb( r ) ),
+            color::to_8bit( color::linear_to_srgb( g ) ),
+            color::to_8bit( color::linear_to_srgb( b ) ),
+            color::to_8bit( a ) );
+    }
+
+    /// Receives the JPEG or PNG stream of bitmaps that embed their pixel data in such a format.
+    /// Implementations may decode the stream themselves, by default it is rejected with an error.
+    fn set_embedded( &mut self, embedded: &Embedded ) -> Result<()> {
+        Err( io::Error::other( format!( "No decoder for embedded {:?} stream", embedded.kind ) ) )
+    }
+
+    /// Receives a metadata entry of a RIFF bitmap, such as the `INAM` entry of its INFO list,
+    /// with trailing zero bytes removed. Does nothing by default.
+    fn set_metadata( &mut self, _id: [u8; 4], _value: &[u8] ) {
+    }
 }
 
 pub fn decode<TBuilder: Builder>(
@@ -67,3 +161,108 @@ pub fn decode<TBuilder: Builder>(
 
     bitmap::decode( input, builder )
 }
+
+/// Reads the headers of a bitmap file without decoding its pixels. Device dependent and RIFF
+/// bitmaps are not supported.
+pub fn read_info( input: &mut Read ) -> Result<BitmapInfo> {
+    info::read_info( input )
+}
+
+/// Reads the embedded or linked color profile of a bitmap file, if there is one.
+pub fn read_profile<TInput: Read + Seek>( input: &mut TInput ) -> Result<Option<Profile>> {
+    profile::read_file_profile( input )
+}
+
+/// Parses the data of a matrix/TRC ICC profile, as returned by `read_profile` for embedded profiles.
+pub fn read_icc( data: &[u8] ) -> Result<IccProfile> {
+    icc::read_icc( data )
+}
+
+/// Decodes a packed device independent bitmap that starts with its info header instead of a
+/// file header, as used by the clipboard, resources and metafiles.
+pub fn decode_dib<TBuilder: Builder>( input: &mut Read, builder: TBuilder ) -> Result<TBuilder> {
+    bitmap::decode_dib( input, builder )
+}
+
+/// Reads the list of images contained in an OS/2 bitmap array.
+pub fn read_array<TInput: Read + Seek>( input: &mut TInput ) -> Result<Vec<ArrayEntry>> {
+    array::read_array( input )
+}
+
+/// Decodes a single image of an OS/2 bitmap array previously listed by `read_array`.
+pub fn decode_array_entry<TInput: Read + Seek, TBuilder: Builder>(
+    input: &mut TInput, entry: &ArrayEntry, builder: TBuilder ) -> Result<TBuilder> {
+
+    array::decode_entry( input, entry, builder )
+}
+
+/// Decodes an OS/2 icon or pointer, combining its colors and masks into a single image.
+pub fn decode_pointer<TInput: Read + Seek, TBuilder: Builder>(
+    input: &mut TInput, builder: TBuilder ) -> Result<TBuilder> {
+
+    pointer::decode( input, builder )
+}
+
+/// Reads the JPEG or PNG stream embedded into a bitmap without decoding it.
+pub fn read_embedded( input: &mut Read ) -> Result<Embedded> {
+    embedded::read( input )
+}
+
+/// Reads the list of images contained in a Windows icon or cursor file.
+pub fn read_icon<TInput: Read + Seek>( input: &mut TInput ) -> Result<Vec<IconEntry>> {
+    icon::read_icon_dir( input )
+}
+
+/// Decodes a single image of a Windows icon or cursor file previously listed by `read_icon`.
+pub fn decode_icon_entry<TInput: Read + Seek, TBuilder: Builder>(
+    input: &mut TInput, entry: &IconEntry, builder: TBuilder ) -> Result<TBuilder> {
+
+    icon::decode_entry( input, entry, builder )
+}
+
+/// Reads the frames and steps of a Windows animated cursor file.
+pub fn read_animation<TInput: Read + Seek>( input: &mut TInput ) -> Result<Animation> {
+    animation::read_animation( input )
+}
+
+/// Decodes a single frame of a Windows animated cursor previously read by `read_animation`.
+pub fn decode_animation_frame<TInput: Read + Seek, TBuilder: Builder>(
+    input: &mut TInput, animation: &Animation, frame: usize, builder: TBuilder ) -> Result<TBuilder> {
+
+    animation::decode_frame( input, animation, frame, builder )
+}
+
+/// Lists the bitmaps drawn by the records of an enhanced or Windows metafile.
+pub fn read_metafile<TInput: Read + Seek>( input: &mut TInput ) -> Result<Vec<MetafileBitmap>> {
+    metafile::read_metafile( input )
+}
+
+/// Decodes a single bitmap of a metafile previously listed by `read_metafile`.
+pub fn decode_metafile_bitmap<TInput: Read + Seek, TBuilder: Builder>(
+    input: &mut TInput, bitmap: &MetafileBitmap, builder: TBuilder ) -> Result<TBuilder> {
+
+    metafile::decode_bitmap( input, bitmap, builder )
+}
+
+/// Lists the bitmap, icon and cursor resources of a Windows executable or library.
+#[cfg( feature = "pe" )]
+pub fn read_resources<TInput: Read + Seek>( input: &mut TInput ) -> Result<Vec<Resource>> {
+    pe::read_resources( input )
+}
+
+/// Decodes a single bitmap, icon or cursor resource previously listed by `read_resources`.
+#[cfg( feature = "pe" )]
+pub fn decode_resource<TInput: Read + Seek, TBuilder: Builder>(
+    input: &mut TInput, resource: &Resource, builder: TBuilder ) -> Result<TBuilder> {
+
+    pe::decode_resource( input, resource, builder )
+}
+
+/// Lists the images of an icon or cursor group resource, which can be decoded with
+/// `decode_icon_entry`. The resources have to be the ones listed by `read_resources`.
+#[cfg( feature = "pe" )]
+pub fn read_resource_group<TInput: Read + Seek>(
+    input: &mut TInput, group: &Resource, resources: &[Resource] ) -> Result<Vec<IconEntry>> {
+
+    pe::read_group( input, group, resources )
+}
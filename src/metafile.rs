@@ -0,0 +1,353 @@
+use std::io::{
+    Result,
+    Read,
+    Seek,
+    SeekFrom,
+};
+
+use byteorder::{
+    ReadBytesExt,
+    LittleEndian,
+};
+
+use bitmap;
+use bitmap::new_data_error;
+
+const EMF_SIGNATURE: u32 = 0x464D4520; // " EMF"
+const EMF_EOF: u32 = 14;
+const WMF_PLACEABLE: u32 = 0x9AC6CDD7;
+const WMF_EOF: u16 = 0x0000;
+const PALETTE_INDICES: u32 = 1; // DIB_PAL_COLORS, the palette holds indices into the logical palette
+
+/// A bitmap drawn by a record of an enhanced or Windows metafile.
+///
+/// The destination rectangle is given in the logical units of the metafile, a negative width
+/// or height mirrors the bitmap.
+#[derive( Debug, PartialEq, Eq, Clone, Copy )]
+pub struct MetafileBitmap {
+    /// Type or function of the record holding the bitmap
+    pub record: u32,
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+    header: u64, // Offset of the info header
+    bits: u64, // Offset of the pixel data
+    bits_size: u64,
+    palette_indices: bool,
+}
+
+// Reads the first bytes of a record, which hold all parameters in front of the bitmap
+fn read_record( input: &mut Read, size: u64 ) -> Result<Vec<u8>> {
+    let mut record = vec![0; size.min( 108 ) as usize];
+    input.read_exact( &mut record )?;
+
+    Ok( record )
+}
+
+fn read_u32( record: &[u8], offset: usize ) -> Result<u32> {
+    ( &record[ offset.min( record.len() ).. ] ).read_u32::<LittleEndian>()
+}
+
+fn read_i32( record: &[u8], offset: usize ) -> Result<i32> {
+    ( &record[ offset.min( record.len() ).. ] ).read_i32::<LittleEndian>()
+}
+
+fn read_i16( record: &[u8], offset: usize ) -> Result<i32> {
+    Ok( ( &record[ offset.min( record.len() ).. ] ).read_i16::<LittleEndian>()? as i32 )
+}
+
+fn read_emf<TInput: Read + Seek>( input: &mut TInput, base: u64 ) -> Result<Vec<MetafileBitmap>> {
+    let mut bitmaps = Vec::new();
+    let mut position = base;
+
+    loop {
+        input.seek( SeekFrom::Start( position ) )?;
+        let kind = input.read_u32::<LittleEndian>()?;
+        let size = input.read_u32::<LittleEndian>()? as u64;
+        if size < 8 || !size.is_multiple_of( 4 ) {
+            return Err( new_data_error( format!( "Invalid EMF record size {}", size ) ) );
+        }
+
+        // All offsets are relative to the start of the record
+        input.seek( SeekFrom::Start( position ) )?;
+        let record = read_record( input, size )?;
+
+        let placement = match kind {
+            EMF_EOF => break,
+            76 | 77 | 78 | 114 | 116 => { // BITBLT, STRETCHBLT, MASKBLT, ALPHABLEND, TRANSPARENTBLT
+                let x = read_i32( &record, 24 )?;
+                let y = read_i32( &record, 28 )?;
+                let width = read_i32( &record, 32 )?;
+                let height = read_i32( &record, 36 )?;
+                Some( ( x, y, width, height, 80, 84 ) )
+            },
+            80 => { // SETDIBITSTODEVICE
+                let x = read_i32( &record, 24 )?;
+                let y = read_i32( &record, 28 )?;
+                let width = read_i32( &record, 40 )?;
+                let height = read_i32( &record, 44 )?;
+                Some( ( x, y, width, height, 64, 48 ) )
+            },
+            81 => { // STRETCHDIBITS
+                let x = read_i32( &record, 24 )?;
+                let y = read_i32( &record, 28 )?;
+                let width = read_i32( &record, 72 )?;
+                let height = read_i32( &record, 76 )?;
+                Some( ( x, y, width, height, 64, 48 ) )
+            },
+            _ => None,
+        };
+
+        // Raster operations without a source have no bitmap
+        if let Some( ( x, y, width, height, usage, offsets ) ) = placement {
+            let header = read_u32( &record, offsets )? as u64;
+            let header_size = read_u32( &record, offsets + 4 )?;
+            let bits = read_u32( &record, offsets + 8 )? as u64;
+            let bits_size = read_u32( &record, offsets + 12 )? as u64;
+
+            if header_size != 0 && bits_size != 0 {
+                if header + header_size as u64 > size || bits + bits_size > size {
+                    return Err( new_data_error( "Invalid EMF bitmap offset" ) );
+                }
+
+                bitmaps.push( MetafileBitmap {
+                    record: kind,
+                    x,
+                    y,
+                    width,
+                    height,
+                    header: position + header,
+                    bits: position + bits,
+                    bits_size,
+                    palette_indices: read_u32( &record, usage )? == PALETTE_INDICES,
+                } );
+            }
+        }
+
+        position += size;
+    }
+
+    Ok( bitmaps )
+}
+
+fn read_wmf<TInput: Read + Seek>( input: &mut TInput, base: u64 ) -> Result<Vec<MetafileBitmap>> {
+    // Read header, whose size is given in 16-bit words
+    input.seek( SeekFrom::Start( base + 2 ) )?;
+    let header_size = input.read_u16::<LittleEndian>()? as u64 * 2;
+
+    let mut bitmaps = Vec::new();
+    let mut position = base + header_size;
+
+    loop {
+        input.seek( SeekFrom::Start( position ) )?;
+        let size = input.read_u32::<LittleEndian>()? as u64 * 2;
+        let function = input.read_u16::<LittleEndian>()?;
+        if size < 6 {
+            return Err( new_data_error( format!( "Invalid WMF record size {}", size ) ) );
+        }
+
+        input.seek( SeekFrom::Start( position ) )?;
+        let record = read_record( input, size )?;
+
+        // Parameters are stored in reverse order, the bitmap follows them.
+        // Records without a source bitmap are as large as their function implies.
+        let placement = match function {
+            WMF_EOF => break,
+            0x0940 if size != ( ( function >> 8 ) as u64 + 3 ) * 2 => { // DIBBITBLT
+                let height = read_i16( &record, 14 )?;
+                let width = read_i16( &record, 16 )?;
+                let y = read_i16( &record, 18 )?;
+                let x = read_i16( &record, 20 )?;
+                Some( ( x, y, width, height, None, 22 ) )
+            },
+            0x0B41 if size != ( ( function >> 8 ) as u64 + 3 ) * 2 => { // DIBSTRETCHBLT
+                let height = read_i16( &record, 18 )?;
+                let width = read_i16( &record, 20 )?;
+                let y = read_i16( &record, 22 )?;
+                let x = read_i16( &record, 24 )?;
+                Some( ( x, y, width, height, None, 26 ) )
+            },
+            0x0F43 => { // STRETCHDIB
+                let height = read_i16( &record, 20 )?;
+                let width = read_i16( &record, 22 )?;
+                let y = read_i16( &record, 24 )?;
+                let x = read_i16( &record, 26 )?;
+                Some( ( x, y, width, height, Some( 10 ), 28 ) )
+            },
+            0x0D33 => { // SETDIBTODEV
+                let height = read_i16( &record, 16 )?;
+                let width = read_i16( &record, 18 )?;
+                let y = read_i16( &record, 20 )?;
+                let x = read_i16( &record, 22 )?;
+                Some( ( x, y, width, height, Some( 6 ), 24 ) )
+            },
+            _ => None,
+        };
+
+        // The packed bitmap makes up the rest of the record
+        if let Some( ( x, y, width, height, usage, offset ) ) = placement {
+            let header = position + offset;
+            input.seek( SeekFrom::Start( header ) )?;
+            let bits = header + bitmap::read_header( input )?.size() as u64;
+
+            if bits > position + size {
+                return Err( new_data_error( "Invalid WMF bitmap size" ) );
+            }
+
+            let palette_indices = match usage {
+                Some( usage ) => ( &record[ usage.. ] ).read_u16::<LittleEndian>()? as u32 == PALETTE_INDICES,
+                None => false,
+            };
+
+            bitmaps.push( MetafileBitmap {
+                record: function as u32,
+                x,
+                y,
+                width,
+                height,
+                header,
+                bits,
+                bits_size: position + size - bits,
+                palette_indices,
+            } );
+        }
+
+        position += size;
+    }
+
+    Ok( bitmaps )
+}
+
+pub( crate ) fn read_metafile<TInput: Read + Seek>( input: &mut TInput ) -> Result<Vec<MetafileBitmap>> {
+    let base = input.stream_position()?;
+    let signature = input.read_u32::<LittleEndian>()?;
+
+    // Enhanced metafiles start with their header record
+    if signature == 1 {
+        input.seek( SeekFrom::Start( base + 40 ) )?;
+        if input.read_u32::<LittleEndian>()? == EMF_SIGNATURE {
+            return read_emf( input, base );
+        }
+    }
+
+    // Windows metafiles may be preceded by a placeable header
+    let base = match signature {
+        WMF_PLACEABLE => base + 22,
+        _ => base,
+    };
+
+    input.seek( SeekFrom::Start( base ) )?;
+    let kind = input.read_u16::<LittleEndian>()?;
+    let header_size = input.read_u16::<LittleEndian>()?;
+
+    match ( kind, header_size ) {
+        ( 1, 9 ) | ( 2, 9 ) => read_wmf( input, base ),
+        _ => Err( new_data_error( "Invalid metafile header" ) ),
+    }
+}
+
+pub( crate ) fn decode_bitmap<TInput: Read + Seek, TBuilder: super::Builder>(
+    input: &mut TInput, bitmap: &MetafileBitmap, mut builder: TBuilder ) -> Result<TBuilder> {
+
+    input.seek( SeekFrom::Start( bitmap.header ) )?;
+    let header = bitmap::read_header( input )?;
+
+    if bitmap.palette_indices && header.core.bpp <= 8 {
+        return Err( new_data_error( "Bitmaps with logical palette indices are not supported" ) );
+    }
+
+    input.seek( SeekFrom::Start( bitmap.bits ) )?;
+    bitmap::decode_pixels( &mut input.take( bitmap.bits_size ), &header, bitmap.bits, &mut builder )?;
+
+    Ok( builder )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use byteorder::{
+        WriteBytesExt,
+        LittleEndian,
+    };
+
+    use bitmap::tests::bitmap;
+    use canvas::Canvas;
+    use super::{
+        read_metafile,
+        decode_bitmap,
+    };
+
+    // A packed 1x1 bitmap with 24 bits per pixel
+    fn dib() -> Vec<u8> {
+        bitmap( 40, 1, 1, 24, 0, &[], &[ 0x10, 0x20, 0x30, 0 ] )[ 14.. ].to_vec()
+    }
+
+    // An enhanced metafile drawing the bitmap with a STRETCHDIBITS record, whose pixel data
+    // has the given size
+    fn emf( bits_size: u32 ) -> Vec<u8> {
+        let mut file = Vec::new();
+        file.write_u32::<LittleEndian>( 1 ).unwrap();
+        file.write_u32::<LittleEndian>( 88 ).unwrap();
+        file.resize( 40, 0 );
+        file.extend_from_slice( b" EMF" );
+        file.resize( 88, 0 );
+
+        for &value in [ 81, 124, 0, 0, 0, 0, 5, 6, 0, 0, 1, 1, 80, 40, 120, bits_size, 0, 0, 2, 3 ].iter() {
+            file.write_u32::<LittleEndian>( value ).unwrap();
+        }
+        file.extend( dib() );
+
+        for &value in [ 14, 20, 0, 0, 20 ].iter() {
+            file.write_u32::<LittleEndian>( value ).unwrap();
+        }
+
+        file
+    }
+
+    #[test]
+    fn reads_emf_bitmap() {
+        let file = emf( 4 );
+        let mut input = Cursor::new( &file[ .. ] );
+        let bitmaps = read_metafile( &mut input ).unwrap();
+
+        assert_eq!( bitmaps.len(), 1 );
+        let bitmap = bitmaps[ 0 ];
+        assert_eq!( ( bitmap.record, bitmap.x, bitmap.y, bitmap.width, bitmap.height ), ( 81, 5, 6, 2, 3 ) );
+
+        let canvas = decode_bitmap( &mut input, &bitmap, Canvas::new() ).unwrap();
+        assert_eq!( canvas.pixel( 0, 0 ), ( 0x30, 0x20, 0x10, 255 ) );
+    }
+
+    #[test]
+    fn emf_bitmap_beyond_record_is_an_error() {
+        let file = emf( 0xFFFFFFFF );
+        assert!( read_metafile( &mut Cursor::new( &file[ .. ] ) ).is_err() );
+    }
+
+    #[test]
+    fn reads_wmf_bitmap() {
+        // Header, STRETCHDIB record and end of file record
+        let mut file = Vec::new();
+        for &value in [ 1, 9, 0x300, 0, 0, 0, 0, 0, 0 ].iter() {
+            file.write_u16::<LittleEndian>( value ).unwrap();
+        }
+        file.write_u32::<LittleEndian>( 36 ).unwrap();
+        for &value in [ 0x0F43, 0, 0, 0, 1, 1, 0, 0, 3, 2, 6, 5 ].iter() {
+            file.write_u16::<LittleEndian>( value ).unwrap();
+        }
+        file.extend( dib() );
+        file.extend_from_slice( &[ 3, 0, 0, 0, 0, 0 ] );
+
+        let mut input = Cursor::new( &file[ .. ] );
+        let bitmaps = read_metafile( &mut input ).unwrap();
+
+        assert_eq!( bitmaps.len(), 1 );
+        let bitmap = bitmaps[ 0 ];
+        assert_eq!( ( bitmap.record, bitmap.x, bitmap.y, bitmap.width, bitmap.height ), ( 0x0F43, 5, 6, 2, 3 ) );
+
+        let canvas = decode_bitmap( &mut input, &bitmap, Canvas::new() ).unwrap();
+        assert_eq!( canvas.pixel( 0, 0 ), ( 0x30, 0x20, 0x10, 255 ) );
+    }
+}
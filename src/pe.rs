@@ -0,0 +1,482 @@
+use std::collections::HashSet;
+use std::io::{
+    Result,
+    Read,
+    Seek,
+    SeekFrom,
+};
+
+use byteorder::{
+    ReadBytesExt,
+    LittleEndian,
+};
+
+use bitmap;
+use bitmap::new_data_error;
+use icon;
+use icon::{
+    IconKind,
+    IconEntry,
+};
+
+const RESOURCE_DIRECTORY: u32 = 2; // Index of the resource table within the data directories
+const SUBDIRECTORY: u32 = 0x8000_0000; // Marks directory entries that point to a subdirectory
+const NAMED: u32 = 0x8000_0000; // Marks directory entries that are identified by a name
+
+/// The type of an image resource within a Windows executable.
+///
+/// Groups list the icons or cursors of different sizes that make up a single icon or cursor.
+#[derive( Debug, PartialEq, Eq, Clone, Copy )]
+pub enum ResourceKind {
+    Cursor, // RT_CURSOR
+    Bitmap, // RT_BITMAP
+    Icon, // RT_ICON
+    CursorGroup, // RT_GROUP_CURSOR
+    IconGroup, // RT_GROUP_ICON
+}
+
+/// Identifies a resource either by number or by name.
+#[derive( Debug, PartialEq, Eq, Clone )]
+pub enum ResourceName {
+    Id( u16 ),
+    Name( String ),
+}
+
+/// A single image resource within a Windows executable or library.
+#[derive( Debug, PartialEq, Eq, Clone )]
+pub struct Resource {
+    pub kind: ResourceKind,
+    pub name: ResourceName,
+    pub language: u16,
+    /// Size of the resource data in bytes
+    pub size: u32,
+    /// Offset of the resource data within the input
+    pub offset: u64,
+}
+
+impl ResourceKind {
+    fn from_id( id: u32 ) -> Option<ResourceKind> {
+        match id {
+            1 => Some( ResourceKind::Cursor ),
+            2 => Some( ResourceKind::Bitmap ),
+            3 => Some( ResourceKind::Icon ),
+            12 => Some( ResourceKind::CursorGroup ),
+            14 => Some( ResourceKind::IconGroup ),
+            _ => None,
+        }
+    }
+}
+
+struct Section {
+    address: u32,
+    size: u32,
+    offset: u32,
+}
+
+// Maps relative virtual addresses to file offsets using the section table
+struct Sections {
+    sections: Vec<Section>,
+}
+
+impl Sections {
+    fn find( &self, address: u32 ) -> Result<&Section> {
+        self.sections.iter()
+            .find( |s| address >= s.address && address - s.address < s.size )
+            .ok_or( new_data_error( format!( "Invalid virtual address 0x{:X}", address ) ) )
+    }
+
+    fn offset( &self, address: u32 ) -> Result<u64> {
+        self.find( address ).map( |s| s.offset as u64 + ( address - s.address ) as u64 )
+    }
+
+    // Returns the file offset at which the section holding the address ends
+    fn end( &self, address: u32 ) -> Result<u64> {
+        self.find( address ).map( |s| s.offset as u64 + s.size as u64 )
+    }
+}
+
+struct DirectoryEntry {
+    id: u32,
+    offset: u32,
+}
+
+// Tracks the directories read while walking the resource tree. Each directory may only be read
+// once and all of them together can't hold more entries than fit into the resource section.
+struct Directories {
+    visited: HashSet<u64>,
+    remaining: u64,
+}
+
+impl Directories {
+    fn new( table: u64, end: u64 ) -> Directories {
+        Directories {
+            visited: HashSet::new(),
+            remaining: end.saturating_sub( table ) / 8,
+        }
+    }
+
+    // Reads the entries of a resource directory, offsets are relative to the resource table
+    fn read<TInput: Read + Seek>( &mut self, input: &mut TInput, offset: u64 )
+        -> Result<Vec<DirectoryEntry>> {
+
+        if !self.visited.insert( offset ) {
+            return Err( new_data_error(
+                format!( "Resource directory at 0x{:X} is referenced twice", offset ) ) );
+        }
+
+        input.seek( SeekFrom::Start( offset + 12 ) )?;
+        let named = input.read_u16::<LittleEndian>()?;
+        let ids = input.read_u16::<LittleEndian>()?;
+
+        let count = named as u64 + ids as u64;
+        if count > self.remaining {
+            return Err( new_data_error( "Resource directories exceed the resource section" ) );
+        }
+        self.remaining -= count;
+
+        let mut entries = Vec::with_capacity( count as usize );
+        for _ in 0..count {
+            let id = input.read_u32::<LittleEndian>()?;
+            let offset = input.read_u32::<LittleEndian>()?;
+
+            entries.push( DirectoryEntry {
+                id,
+                offset,
+            } );
+        }
+
+        Ok( entries )
+    }
+}
+
+fn read_name<TInput: Read + Seek>( input: &mut TInput, table: u64, id: u32 ) -> Result<ResourceName> {
+    if id & NAMED == 0 {
+        return Ok( ResourceName::Id( id as u16 ) );
+    }
+
+    // Names are stored as UTF-16 strings prefixed by their length
+    input.seek( SeekFrom::Start( table + ( id & !NAMED ) as u64 ) )?;
+    let length = input.read_u16::<LittleEndian>()?;
+
+    let mut name = Vec::with_capacity( length as usize );
+    for _ in 0..length {
+        name.push( input.read_u16::<LittleEndian>()? );
+    }
+
+    Ok( ResourceName::Name( String::from_utf16_lossy( &name ) ) )
+}
+
+fn read_sections<TInput: Read + Seek>( input: &mut TInput ) -> Result<( Sections, u32 )> {
+    // Read DOS header
+    let mut signature = [0; 2];
+    input.read_exact( &mut signature )?;
+    if &signature != b"MZ" {
+        return Err( new_data_error( "Invalid executable header" ) );
+    }
+
+    input.seek( SeekFrom::Start( 0x3C ) )?;
+    let header = input.read_u32::<LittleEndian>()? as u64;
+
+    // Read file header
+    let mut signature = [0; 4];
+    input.seek( SeekFrom::Start( header ) )?;
+    input.read_exact( &mut signature )?;
+    if &signature != b"PE\0\0" {
+        return Err( new_data_error( "Executable is not a portable executable" ) );
+    }
+
+    input.read_u16::<LittleEndian>()?; // Machine
+    let count = input.read_u16::<LittleEndian>()?;
+    input.seek( SeekFrom::Current( 12 ) )?; // Time stamp and symbol table
+    let optional_size = input.read_u16::<LittleEndian>()?;
+    input.read_u16::<LittleEndian>()?; // Characteristics
+
+    // Read optional header, whose data directories depend on the address width
+    let optional = header + 24;
+    let directories = match input.read_u16::<LittleEndian>()? {
+        0x10B => 96, // PE32
+        0x20B => 112, // PE32+
+        x => return Err( new_data_error(
+            format!( "Invalid optional header type 0x{:X}", x ) ) ),
+    };
+
+    input.seek( SeekFrom::Start( optional + directories - 4 ) )?;
+    let directory_count = input.read_u32::<LittleEndian>()?;
+    if directory_count <= RESOURCE_DIRECTORY {
+        return Err( new_data_error( "Executable has no resources" ) );
+    }
+
+    input.seek( SeekFrom::Current( RESOURCE_DIRECTORY as i64 * 8 ) )?;
+    let resources = input.read_u32::<LittleEndian>()?;
+    if resources == 0 {
+        return Err( new_data_error( "Executable has no resources" ) );
+    }
+
+    // Read section table
+    input.seek( SeekFrom::Start( optional + optional_size as u64 ) )?;
+    let mut sections = Vec::with_capacity( count as usize );
+    for _ in 0..count {
+        input.seek( SeekFrom::Current( 8 ) )?; // Name
+        let virtual_size = input.read_u32::<LittleEndian>()?;
+        let address = input.read_u32::<LittleEndian>()?;
+        let raw_size = input.read_u32::<LittleEndian>()?;
+        let offset = input.read_u32::<LittleEndian>()?;
+        input.seek( SeekFrom::Current( 16 ) )?; // Relocations, line numbers and characteristics
+
+        sections.push( Section {
+            address,
+            size: virtual_size.max( raw_size ),
+            offset,
+        } );
+    }
+
+    Ok( ( Sections { sections }, resources ) )
+}
+
+pub( crate ) fn read_resources<TInput: Read + Seek>( input: &mut TInput ) -> Result<Vec<Resource>> {
+    let ( sections, address ) = read_sections( input )?;
+    let table = sections.offset( address )?;
+    let mut directories = Directories::new( table, sections.end( address )? );
+
+    // The resource tree is ordered by type, name and language
+    let mut resources = Vec::new();
+    for kind_entry in directories.read( input, table )? {
+        let kind = match ResourceKind::from_id( kind_entry.id ) {
+            Some( kind ) if kind_entry.offset & SUBDIRECTORY != 0 => kind,
+            _ => continue,
+        };
+
+        let names = table + ( kind_entry.offset & !SUBDIRECTORY ) as u64;
+        for name_entry in directories.read( input, names )? {
+            if name_entry.offset & SUBDIRECTORY == 0 {
+                continue;
+            }
+
+            let name = read_name( input, table, name_entry.id )?;
+            let languages = table + ( name_entry.offset & !SUBDIRECTORY ) as u64;
+
+            for language_entry in directories.read( input, languages )? {
+                if language_entry.offset & SUBDIRECTORY != 0 {
+                    continue;
+                }
+
+                // Read data entry
+                input.seek( SeekFrom::Start( table + language_entry.offset as u64 ) )?;
+                let data = input.read_u32::<LittleEndian>()?;
+                let size = input.read_u32::<LittleEndian>()?;
+
+                resources.push( Resource {
+                    kind,
+                    name: name.clone(),
+                    language: language_entry.id as u16,
+                    size,
+                    offset: sections.offset( data )?,
+                } );
+            }
+        }
+    }
+
+    Ok( resources )
+}
+
+// Cursor resources are prefixed by their hotspot
+fn read_hotspot<TInput: Read + Seek>( input: &mut TInput, resource: &Resource ) -> Result<( u16, u16 )> {
+    if resource.size < 4 {
+        return Err( new_data_error( "Invalid cursor resource size" ) );
+    }
+
+    input.seek( SeekFrom::Start( resource.offset ) )?;
+    let x = input.read_u16::<LittleEndian>()?;
+    let y = input.read_u16::<LittleEndian>()?;
+
+    Ok( ( x, y ) )
+}
+
+pub( crate ) fn decode_resource<TInput: Read + Seek, TBuilder: super::Builder>(
+    input: &mut TInput, resource: &Resource, builder: TBuilder ) -> Result<TBuilder> {
+
+    match resource.kind {
+        ResourceKind::Bitmap => {
+            input.seek( SeekFrom::Start( resource.offset ) )?;
+            bitmap::decode_dib( &mut input.take( resource.size as u64 ), builder )
+        },
+        ResourceKind::Icon => icon::decode_image(
+            input, resource.offset, resource.size, None, builder ),
+        ResourceKind::Cursor => {
+            let hotspot = read_hotspot( input, resource )?;
+            icon::decode_image( input, resource.offset + 4, resource.size - 4, Some( hotspot ), builder )
+        },
+        ResourceKind::CursorGroup | ResourceKind::IconGroup => Err( new_data_error(
+            "Resource groups have to be read with bmp_rs::read_resource_group" ) ),
+    }
+}
+
+// Lists the images of an icon or cursor group, which refers to them by their numeric name.
+// Images of the group's language are preferred over those of other languages.
+pub( crate ) fn read_group<TInput: Read + Seek>(
+    input: &mut TInput, group: &Resource, resources: &[Resource] ) -> Result<Vec<IconEntry>> {
+
+    let ( kind, image_kind ) = match group.kind {
+        ResourceKind::IconGroup => ( IconKind::Icon, ResourceKind::Icon ),
+        ResourceKind::CursorGroup => ( IconKind::Cursor, ResourceKind::Cursor ),
+        _ => return Err( new_data_error( "Resource is not an icon or cursor group" ) ),
+    };
+
+    // Read group header
+    input.seek( SeekFrom::Start( group.offset ) )?;
+    input.read_u16::<LittleEndian>()?; // Reserved
+    input.read_u16::<LittleEndian>()?; // Type
+    let count = input.read_u16::<LittleEndian>()?;
+
+    // Read group entries, which differ from icon directory entries in their dimensions
+    let mut images = Vec::with_capacity( count as usize );
+    for _ in 0..count {
+        let ( width, height ) = match kind {
+            IconKind::Icon => {
+                let width = input.read_u8()? as u32;
+                let height = input.read_u8()? as u32;
+                input.read_u16::<LittleEndian>()?; // Color count and reserved
+
+                ( if width == 0 { 256 } else { width }, if height == 0 { 256 } else { height } )
+            },
+            IconKind::Cursor => {
+                let width = input.read_u16::<LittleEndian>()? as u32;
+                let height = input.read_u16::<LittleEndian>()? as u32 / 2; // Includes the mask
+                ( width, height )
+            },
+        };
+
+        input.read_u16::<LittleEndian>()?; // Planes
+        input.read_u16::<LittleEndian>()?; // Bits per pixel
+        input.read_u32::<LittleEndian>()?; // Size
+        let id = input.read_u16::<LittleEndian>()?;
+
+        images.push( ( width, height, id ) );
+    }
+
+    // Look up images
+    let mut entries = Vec::with_capacity( images.len() );
+    for ( width, height, id ) in images {
+        let name = ResourceName::Id( id );
+        let image = resources.iter()
+            .filter( |r| r.kind == image_kind && r.name == name )
+            .max_by_key( |r| r.language == group.language )
+            .ok_or( new_data_error( format!( "Missing resource {} of group", id ) ) )?;
+
+        let mut entry = IconEntry {
+            kind,
+            width,
+            height,
+            bpp: 0,
+            hotspot: None,
+            png: false,
+            size: image.size,
+            offset: image.offset,
+        };
+
+        if kind == IconKind::Cursor {
+            entry.hotspot = Some( read_hotspot( input, image )? );
+            entry.size -= 4;
+            entry.offset += 4;
+        }
+
+        icon::read_image_format( input, &mut entry )?;
+        entries.push( entry );
+    }
+
+    Ok( entries )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use byteorder::{
+        WriteBytesExt,
+        LittleEndian,
+    };
+
+    use bitmap::tests::bitmap;
+    use canvas::Canvas;
+    use super::{
+        read_resources,
+        decode_resource,
+        ResourceKind,
+        ResourceName,
+        SUBDIRECTORY,
+    };
+
+    // An executable whose resource section at 0x200 holds a single 1x1 bitmap resource. The type
+    // directory holds the given number of entries and the name entry points to the given offset.
+    fn executable( types: u16, languages: u32 ) -> Vec<u8> {
+        let mut file = b"MZ".to_vec();
+        file.resize( 0x3C, 0 );
+        file.write_u32::<LittleEndian>( 0x40 ).unwrap();
+
+        // File header with a PE32 optional header holding three data directories
+        file.extend_from_slice( b"PE\0\0" );
+        file.write_u16::<LittleEndian>( 0x14C ).unwrap();
+        file.write_u16::<LittleEndian>( 1 ).unwrap();
+        file.resize( 0x54, 0 );
+        file.write_u16::<LittleEndian>( 120 ).unwrap();
+        file.write_u16::<LittleEndian>( 0 ).unwrap();
+        file.write_u16::<LittleEndian>( 0x10B ).unwrap();
+        file.resize( 0x58 + 92, 0 );
+        for &value in [ 3, 0, 0, 0, 0, 0x1000, 0x84 ].iter() {
+            file.write_u32::<LittleEndian>( value ).unwrap();
+        }
+
+        // Section table
+        file.extend_from_slice( b".rsrc\0\0\0" );
+        for &value in [ 0x84, 0x1000, 0x84, 0x200, 0, 0, 0, 0 ].iter() {
+            file.write_u32::<LittleEndian>( value ).unwrap();
+        }
+        file.resize( 0x200, 0 );
+
+        // Type, name and language directories followed by the data entry
+        for &( count, id, offset ) in [
+            ( types, 2, SUBDIRECTORY | 0x18 ),
+            ( 1, 7, SUBDIRECTORY | languages ),
+            ( 1, 0x409, 0x48 ),
+        ].iter() {
+            file.resize( file.len() + 14, 0 );
+            file.write_u16::<LittleEndian>( count ).unwrap();
+            file.write_u32::<LittleEndian>( id ).unwrap();
+            file.write_u32::<LittleEndian>( offset ).unwrap();
+        }
+        for &value in [ 0x1058, 44, 0, 0 ].iter() {
+            file.write_u32::<LittleEndian>( value ).unwrap();
+        }
+
+        file.extend_from_slice( &bitmap( 40, 1, 1, 24, 0, &[], &[ 0x10, 0x20, 0x30, 0 ] )[ 14.. ] );
+        file
+    }
+
+    #[test]
+    fn reads_and_decodes_bitmap_resource() {
+        let file = executable( 1, 0x30 );
+        let mut input = Cursor::new( &file[ .. ] );
+        let resources = read_resources( &mut input ).unwrap();
+
+        assert_eq!( resources.len(), 1 );
+        assert_eq!( resources[ 0 ].kind, ResourceKind::Bitmap );
+        assert_eq!( resources[ 0 ].name, ResourceName::Id( 7 ) );
+        assert_eq!( resources[ 0 ].language, 0x409 );
+        assert_eq!( ( resources[ 0 ].size, resources[ 0 ].offset ), ( 44, 0x258 ) );
+
+        let canvas = decode_resource( &mut input, &resources[ 0 ], Canvas::new() ).unwrap();
+        assert_eq!( canvas.pixel( 0, 0 ), ( 0x30, 0x20, 0x10, 255 ) );
+    }
+
+    #[test]
+    fn looping_directories_are_an_error() {
+        let file = executable( 1, 0x18 );
+        assert!( read_resources( &mut Cursor::new( &file[ .. ] ) ).is_err() );
+    }
+
+    #[test]
+    fn entries_beyond_resource_section_are_an_error() {
+        let file = executable( 0xFFFF, 0x30 );
+        assert!( read_resources( &mut Cursor::new( &file[ .. ] ) ).is_err() );
+    }
+}
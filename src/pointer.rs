@@ -0,0 +1,143 @@
+use std::io::{
+    Result,
+    Read,
+    Seek,
+    SeekFrom,
+};
+
+use bitmap;
+use bitmap::{
+    FileType,
+    FileHeader,
+    Header,
+    new_data_error,
+};
+use canvas::Canvas;
+
+// Headers of an OS/2 icon or pointer. The mask bitmap is twice the height of the image
+// and holds the AND mask in its upper and the XOR mask in its lower half. Color variants
+// carry a second bitmap with the actual colors.
+pub( crate ) struct PointerHeader {
+    file: FileHeader,
+    mask: Header,
+    color: Option<( FileHeader, Header )>,
+}
+
+impl PointerHeader {
+    // Returns width, height and bits per pixel of the final image
+    pub( crate ) fn dimensions( &self ) -> ( u32, u32, u32 ) {
+        match self.color {
+            Some( ( _, ref color ) ) => ( color.core.width, color.core.height, color.core.bpp ),
+            None => ( self.mask.core.width, self.mask.core.height / 2, 1 ),
+        }
+    }
+}
+
+pub( crate ) fn read_header( input: &mut Read, file: FileHeader ) -> Result<PointerHeader> {
+    // Read mask header
+    let mask = bitmap::read_header( input )?;
+    if mask.core.bpp != 1 {
+        return Err( new_data_error(
+            format!( "Invalid bits per pixel {} for pointer mask", mask.core.bpp ) ) );
+    }
+
+    // Read color header
+    let color = match file.file_type {
+        FileType::ColorIcon | FileType::ColorPointer => {
+            let color_file = bitmap::read_file_header( input )?;
+            if color_file.file_type != file.file_type {
+                return Err( new_data_error( "Invalid pointer color header" ) );
+            }
+
+            let color = bitmap::read_header( input )?;
+            if color.core.width != mask.core.width || color.core.height != mask.core.height / 2 {
+                return Err( new_data_error( format!(
+                    "Pointer color size {}x{} doesn't match mask size {}x{}",
+                    color.core.width, color.core.height, mask.core.width, mask.core.height / 2 ) ) );
+            }
+
+            Some( ( color_file, color ) )
+        },
+        _ => None,
+    };
+
+    Ok( PointerHeader {
+        file,
+        mask,
+        color,
+    } )
+}
+
+pub( crate ) fn decode_pixels<TInput: Read + Seek, TBuilder: super::Builder>(
+    input: &mut TInput, base: u64, header: &PointerHeader, mut builder: TBuilder ) -> Result<TBuilder> {
+
+    let width = header.mask.core.width;
+    let height = header.mask.core.height / 2;
+    Canvas::check_size( width, header.mask.core.height )?;
+
+    // Read AND and XOR masks
+    let mut mask = Canvas::new();
+    input.seek( SeekFrom::Start( base + header.file.data_offset as u64 ) )?;
+    bitmap::decode_mask( input, width, height * 2, header.mask.core.top_down, &mut mask )?;
+
+    // Read colors, monochrome images take them from the XOR mask and its palette
+    let mut colors = Canvas::new();
+    let color_row = match header.color {
+        Some( ( ref file, ref color ) ) => {
+            let offset = base + file.data_offset as u64;
+            input.seek( SeekFrom::Start( offset ) )?;
+            bitmap::decode_pixels( input, color, offset, &mut colors )?;
+            0
+        },
+        None => {
+            let offset = base + header.file.data_offset as u64;
+            input.seek( SeekFrom::Start( offset ) )?;
+            bitmap::decode_pixels( input, &header.mask, offset, &mut colors )?;
+            height
+        },
+    };
+
+    builder.set_size( width, height );
+
+    // The hotspot is measured from the bottom left corner
+    let hotspot_x = ( header.file.hotspot_x.max( 0 ) as u32 ).min( width.saturating_sub( 1 ) );
+    let hotspot_y = height.saturating_sub( 1 ).saturating_sub( header.file.hotspot_y.max( 0 ) as u32 );
+    builder.set_hotspot( hotspot_x, hotspot_y );
+
+    for y in 0..height {
+        for x in 0..width {
+            let ( and, _, _, _ ) = mask.pixel( x, y );
+            let ( xor, _, _, _ ) = mask.pixel( x, y + height );
+
+            if and == 0 {
+                let ( r, g, b, _ ) = colors.pixel( x, y + color_row );
+                builder.set_pixel( x, y, r, g, b, 255 );
+
+            } else if xor == 0 {
+                builder.set_pixel( x, y, 0, 0, 0, 0 );
+
+            } else {
+                builder.set_pixel( x, y, 0, 0, 0, 255 );
+                builder.set_inverted( x, y );
+            }
+        }
+    }
+
+    Ok( builder )
+}
+
+pub( crate ) fn decode<TInput: Read + Seek, TBuilder: super::Builder>(
+    input: &mut TInput, builder: TBuilder ) -> Result<TBuilder> {
+
+    // All offsets are relative to the start of the file
+    let base = input.stream_position()?;
+    let file = bitmap::read_file_header( input )?;
+
+    match file.file_type {
+        FileType::ColorIcon | FileType::ColorPointer | FileType::Icon | FileType::Pointer => {
+            let header = read_header( input, file )?;
+            decode_pixels( input, base, &header, builder )
+        },
+        _ => Err( new_data_error( "Invalid icon or pointer file type" ) ),
+    }
+}
@@ -0,0 +1,118 @@
+use std::io::{
+    Result,
+    Read,
+    Seek,
+    SeekFrom,
+};
+
+use bitmap;
+use bitmap::{
+    new_data_error,
+    ColorSpace,
+    FileType,
+    Header,
+    FILE_HEADER_SIZE,
+};
+
+/// The color profile of a bitmap with a Version 5 header.
+#[derive( Debug, PartialEq, Eq, Clone )]
+pub enum Profile {
+    /// The data of an ICC profile stored within the file
+    Embedded( Vec<u8> ),
+    /// The file name of an ICC profile stored elsewhere
+    Linked( String ),
+}
+
+// Reads the profile of a bitmap whose info header starts at the given offset
+pub( crate ) fn read_profile<TInput: Read + Seek>( input: &mut TInput, offset: u64, header: &Header )
+    -> Result<Option<Profile>> {
+
+    let ( color_space, profile ) = match ( &header.extra, &header.profile ) {
+        ( Some( e ), Some( p ) ) => ( ColorSpace::from_u32( e.color_space_type ), p ),
+        _ => return Ok( None ),
+    };
+
+    if color_space != Some( ColorSpace::Linked ) && color_space != Some( ColorSpace::Embedded ) {
+        return Ok( None );
+    }
+
+    // Read profile data, which is located relative to the info header
+    let mut data = Vec::new();
+    input.seek( SeekFrom::Start( offset + profile.data as u64 ) )?;
+    input.take( profile.size as u64 ).read_to_end( &mut data )?;
+
+    if profile.size == 0 || data.len() != profile.size as usize {
+        return Err( new_data_error( "Invalid color profile size" ) );
+    }
+
+    match color_space {
+        Some( ColorSpace::Embedded ) => Ok( Some( Profile::Embedded( data ) ) ),
+        _ => {
+            // Linked file names are zero terminated and use a Windows code page, which is read as Latin-1
+            let name = data.iter()
+                .take_while( |&&c| c != 0 )
+                .map( |&c| c as char )
+                .collect();
+
+            Ok( Some( Profile::Linked( name ) ) )
+        },
+    }
+}
+
+pub( crate ) fn read_file_profile<TInput: Read + Seek>( input: &mut TInput ) -> Result<Option<Profile>> {
+    let base = input.stream_position()?;
+
+    let file = bitmap::read_file_header( input )?;
+    if file.file_type != FileType::DeviceIndependentBitmap {
+        return Err( new_data_error( "Only bitmap files have color profiles" ) );
+    }
+
+    let header = bitmap::read_header( input )?;
+    read_profile( input, base + FILE_HEADER_SIZE, &header )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use byteorder::{
+        ByteOrder,
+        LittleEndian,
+    };
+
+    use bitmap::tests::bitmap;
+    use super::{
+        read_file_profile,
+        Profile,
+    };
+
+    // A bitmap with a Version 5 header whose color profile follows the pixels
+    fn profiled( color_space: &[u8; 4], profile: &[u8], size: u32 ) -> Vec<u8> {
+        let mut file = bitmap( 124, 1, 1, 24, 0, &[], &[ 0, 0, 0, 0 ] );
+        let offset = file.len() as u32 - 14;
+
+        file[ 14 + 56..14 + 60 ].copy_from_slice( color_space );
+        LittleEndian::write_u32( &mut file[ 14 + 112..14 + 116 ], offset );
+        LittleEndian::write_u32( &mut file[ 14 + 116..14 + 120 ], size );
+        file.extend_from_slice( profile );
+
+        file
+    }
+
+    #[test]
+    fn reads_embedded_and_linked_profiles() {
+        let file = profiled( b"DEBM", b"acsp", 4 );
+        let profile = read_file_profile( &mut Cursor::new( &file[ .. ] ) ).unwrap();
+        assert_eq!( profile, Some( Profile::Embedded( b"acsp".to_vec() ) ) );
+
+        let file = profiled( b"KNIL", b"C:\\sRGB.icm\0\0", 13 );
+        let profile = read_file_profile( &mut Cursor::new( &file[ .. ] ) ).unwrap();
+        assert_eq!( profile, Some( Profile::Linked( "C:\\sRGB.icm".to_string() ) ) );
+    }
+
+    #[test]
+    fn profile_size_beyond_input_is_an_error() {
+        let file = profiled( b"DEBM", b"acsp", 0xFFFFFFFF );
+        assert!( read_file_profile( &mut Cursor::new( &file[ .. ] ) ).is_err() );
+    }
+}
@@ -0,0 +1,136 @@
+use std::io;
+use std::io::{
+    Result,
+    Read,
+};
+
+use byteorder::{
+    ReadBytesExt,
+    LittleEndian,
+};
+
+use bitmap;
+use bitmap::new_data_error;
+
+pub( crate ) struct ChunkHeader {
+    pub( crate ) id: [u8; 4],
+    pub( crate ) size: u32,
+}
+
+impl ChunkHeader {
+    pub( crate ) fn from_reader( input: &mut Read ) -> Result<ChunkHeader> {
+        let id = read_id( input )?;
+        let size = input.read_u32::<LittleEndian>()?;
+
+        Ok( ChunkHeader {
+            id,
+            size,
+        } )
+    }
+
+    // Chunks are aligned to 16-bit boundaries
+    pub( crate ) fn padded_size( &self ) -> u64 {
+        self.size as u64 + ( self.size % 2 ) as u64
+    }
+}
+
+pub( crate ) fn read_id( input: &mut Read ) -> Result<[u8; 4]> {
+    let mut id = [0; 4];
+    input.read_exact( &mut id )?;
+
+    Ok( id )
+}
+
+// Reads the value of a chunk, removing the zero bytes that terminate text values
+fn read_value( input: &mut Read, size: u32 ) -> Result<Vec<u8>> {
+    let mut value = Vec::new();
+    input.take( size as u64 ).read_to_end( &mut value )?;
+    if value.len() != size as usize {
+        return Err( new_data_error( "Unexpected end of RIFF chunk" ) );
+    }
+
+    while value.last() == Some( &0 ) {
+        value.pop();
+    }
+
+    Ok( value )
+}
+
+// Decodes a bitmap wrapped in a RIFF "RDIB" form, whose first two bytes were already read as its
+// file type. The data chunk holds either a bitmap file or a packed bitmap, the entries of INFO
+// lists are passed to the builder as metadata.
+pub( crate ) fn decode<TBuilder: super::Builder>(
+    input: &mut Read, mut builder: TBuilder ) -> Result<TBuilder> {
+
+    // Read RIFF header
+    let mut signature = [0; 2];
+    input.read_exact( &mut signature )?;
+    let size = input.read_u32::<LittleEndian>()?;
+
+    if &signature != b"FF" || &read_id( input )? != b"RDIB" {
+        return Err( new_data_error( "Invalid RIFF bitmap header" ) );
+    }
+
+    // Read chunks
+    let mut remaining = ( size as u64 ).saturating_sub( 4 );
+    let mut decoded = false;
+
+    while remaining >= 8 {
+        let chunk = ChunkHeader::from_reader( input )?;
+        let mut data = input.take( chunk.padded_size() );
+
+        match &chunk.id {
+            b"data" if !decoded => {
+                builder = decode_data( &mut data, builder )?;
+                decoded = true;
+            },
+            b"LIST" if chunk.size >= 4 && &read_id( &mut data )? == b"INFO" => {
+                let mut list = chunk.size as u64 - 4;
+                while list >= 8 {
+                    let entry = ChunkHeader::from_reader( &mut data )?;
+                    let value = read_value( &mut data, entry.size )?;
+                    builder.set_metadata( entry.id, &value );
+
+                    // Skip padding
+                    io::copy( &mut ( &mut data ).take( entry.padded_size() - entry.size as u64 ),
+                        &mut io::sink() )?;
+                    list = list.saturating_sub( 8 + entry.padded_size() );
+                }
+            },
+            _ => {}, // Unknown chunks are skipped
+        }
+
+        // Skip the rest of the chunk
+        io::copy( &mut data, &mut io::sink() )?;
+        remaining = remaining.saturating_sub( 8 + chunk.padded_size() );
+    }
+
+    match decoded {
+        true => Ok( builder ),
+        false => Err( new_data_error( "RIFF bitmap has no data chunk" ) ),
+    }
+}
+
+fn decode_data<TBuilder: super::Builder>( input: &mut Read, builder: TBuilder ) -> Result<TBuilder> {
+    let mut signature = [0; 2];
+    input.read_exact( &mut signature )?;
+
+    // Put the signature back in front of the remaining data
+    let mut data = ( &signature[ .. ] ).chain( input );
+    match &signature {
+        b"BM" => bitmap::decode( &mut data, builder ),
+        _ => bitmap::decode_dib( &mut data, builder ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::read_value;
+
+    #[test]
+    fn value_beyond_chunk_is_an_error() {
+        let data = b"name\0\0";
+        assert_eq!( read_value( &mut &data[ .. ], 6 ).unwrap(), b"name" );
+        assert!( read_value( &mut &data[ .. ], 0xFFFFFFFF ).is_err() );
+    }
+}